@@ -1,15 +1,25 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+#[cfg(target_os = "linux")]
+use claude_utils::clipboard::WaylandClipboard;
 use claude_utils::{
     clipboard::{
+        delivery::Delivery,
+        events::{EventBus, LagPolicy},
+        filter::WatchFilter,
         processor::{ClipboardProcessor, ProcessorConfig},
+        rules::RuleSet,
         watcher::ClipboardWatcher,
-        ClipboardManager,
+        ClipboardContent, ClipboardManager, FileClipboard, MemoryClipboard, Selection,
+        SystemClipboard,
     },
+    config::Config,
     file_manager::{FileManager, FileManagerConfig},
     mcp::{
         auth::{AuthConfig, AuthManager},
+        client::DaemonClient,
         server::McpServer,
     },
+    plugins::PluginHost,
     Result, DEFAULT_HOST, DEFAULT_PORT,
 };
 use std::path::PathBuf;
@@ -26,10 +36,59 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
     author
 )]
 struct Cli {
+    /// Clipboard backend to use
+    #[arg(long, global = true, value_enum, default_value_t = Backend::Auto)]
+    backend: Backend,
+
+    /// Clipboard file for the file backend (default: ~/.claude-utils/clipboard.json)
+    #[arg(long, global = true)]
+    clipboard_file: Option<PathBuf>,
+
+    /// Config file (default: ~/.claude-utils/config.toml)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum Backend {
+    /// The desktop clipboard, or the file backend when no display is present
+    Auto,
+    /// The desktop clipboard
+    System,
+    /// The Wayland clipboard via the data-control protocols (wlroots, KDE)
+    #[cfg(target_os = "linux")]
+    Wayland,
+    /// A clipboard file shared by every process on this host
+    File,
+    /// A clipboard that lives only inside this process (for testing)
+    Memory,
+}
+
+fn open_clipboard(backend: Backend, clipboard_file: Option<PathBuf>) -> Result<ClipboardManager> {
+    let backend = match backend {
+        Backend::Auto if SystemClipboard::is_available() => Backend::System,
+        Backend::Auto => {
+            info!("No display detected, using the file clipboard backend");
+            Backend::File
+        }
+        other => other,
+    };
+
+    match backend {
+        Backend::Auto | Backend::System => ClipboardManager::new(),
+        #[cfg(target_os = "linux")]
+        Backend::Wayland => Ok(ClipboardManager::with_backend(WaylandClipboard::new()?)),
+        Backend::File => {
+            let path = clipboard_file.unwrap_or_else(FileClipboard::default_path);
+            Ok(ClipboardManager::with_backend(FileClipboard::new(path)?))
+        }
+        Backend::Memory => Ok(ClipboardManager::with_backend(MemoryClipboard::new())),
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Start the clipboard daemon
@@ -54,14 +113,28 @@ enum Commands {
         #[arg(long)]
         write: bool,
 
-        /// Enable clipboard watching mode
+        /// Enable watch mode: stage copied images and large text
         #[arg(short, long)]
         watch: bool,
 
+        /// Also watch the PRIMARY selection (X11/Wayland)
+        #[arg(long, requires = "watch")]
+        watch_primary: bool,
+
+        /// Poll interval in milliseconds right after a change; polling backs
+        /// off while idle (default from the config file: 250)
+        #[arg(long, value_name = "MS")]
+        poll_interval: Option<u64>,
+
         /// Custom symlink directory (default: ~/Desktop)
         #[arg(long)]
         symlink_dir: Option<PathBuf>,
 
+        /// How staged files reach the symlink directory: symlink, hardlink
+        /// or copy (default from the config file: symlink)
+        #[arg(long, value_name = "MODE")]
+        delivery: Option<Delivery>,
+
         /// Disable dual-format clipboard (path + image)
         #[arg(long)]
         no_dual_format: bool,
@@ -69,6 +142,10 @@ enum Commands {
         /// Disable notifications
         #[arg(long)]
         no_notifications: bool,
+
+        /// Serve MCP over stdin/stdout instead of HTTP
+        #[arg(long)]
+        stdio: bool,
     },
 
     /// Show authentication token
@@ -85,7 +162,38 @@ enum Commands {
     Clip {
         #[command(subcommand)]
         action: ClipAction,
+
+        /// Port of a running daemon to hand clipboard writes to
+        #[arg(long, default_value_t = DEFAULT_PORT)]
+        port: u16,
     },
+
+    /// Pause, resume or inspect the running daemon's clipboard watcher
+    Watch {
+        #[command(subcommand)]
+        action: WatchAction,
+
+        /// Port of the running daemon
+        #[arg(long, default_value_t = DEFAULT_PORT)]
+        port: u16,
+    },
+
+    /// Owns a selection until another application replaces it
+    #[command(hide = true)]
+    HoldSelection {
+        #[arg(long, default_value_t = Selection::Clipboard)]
+        selection: Selection,
+    },
+}
+
+#[derive(Subcommand)]
+enum WatchAction {
+    /// Stop capturing copies, e.g. during a screen share
+    Pause,
+    /// Start capturing copies again
+    Resume,
+    /// Show whether the watcher is paused and how it polls
+    Status,
 }
 
 #[derive(Subcommand)]
@@ -95,10 +203,43 @@ enum ClipAction {
         /// Output format (json, text)
         #[arg(short, long, default_value = "json")]
         format: String,
+
+        /// Selection to read (clipboard, primary, secondary)
+        #[arg(short, long, default_value_t = Selection::Clipboard)]
+        selection: Selection,
+    },
+
+    /// List every target the clipboard owner offers, with sizes
+    Formats {
+        /// Selection to inspect (clipboard, primary, secondary)
+        #[arg(short, long, default_value_t = Selection::Clipboard)]
+        selection: Selection,
+
+        /// Output format (json, text)
+        #[arg(short, long, default_value = "text")]
+        format: String,
     },
 
     /// Paste clipboard content (outputs path if image)
-    Paste,
+    Paste {
+        /// Selection to read (clipboard, primary, secondary)
+        #[arg(short, long, default_value_t = Selection::Clipboard)]
+        selection: Selection,
+    },
+
+    /// Copy text or a file to the clipboard (reads stdin when neither is given)
+    Set {
+        /// Text to copy
+        text: Option<String>,
+
+        /// Copy an image (PNG, JPEG, WebP, ...) or text file instead
+        #[arg(long, conflicts_with = "text")]
+        file: Option<PathBuf>,
+
+        /// Selection to write (clipboard, primary, secondary)
+        #[arg(short, long, default_value_t = Selection::Clipboard)]
+        selection: Selection,
+    },
 }
 
 #[tokio::main]
@@ -109,7 +250,8 @@ async fn main() -> Result<()> {
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| "claude_utils=info".into()),
         )
-        .with(tracing_subscriber::fmt::layer())
+        // Logs go to stderr so stdout stays clean for MCP stdio and `clip` output
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
         .init();
 
     let cli = Cli::parse();
@@ -122,14 +264,21 @@ async fn main() -> Result<()> {
             staging_dir,
             write,
             watch,
+            watch_primary,
+            poll_interval,
             symlink_dir,
+            delivery,
             no_dual_format,
             no_notifications,
+            stdio,
         } => {
             info!("Starting Claude-Utils clipboard daemon...");
 
+            let config = Config::load_or_default(cli.config.as_deref())?;
+
             // Initialize components
-            let clipboard = Arc::new(ClipboardManager::new()?);
+            let clipboard = Arc::new(open_clipboard(cli.backend, cli.clipboard_file.clone())?);
+            info!("Using {} clipboard backend", clipboard.backend_name());
 
             let file_config = if let Some(dir) = staging_dir {
                 FileManagerConfig {
@@ -149,12 +298,48 @@ async fn main() -> Result<()> {
 
             let auth_manager = AuthManager::new(auth_config).await?;
 
-            if let Some(token) = auth_manager.get_token().await {
-                info!("Authentication token: {}", token);
-                info!("Set CLAUDE_UTILS_TOKEN={} in your environment", token);
+            // The stdio transport is a private pipe to the parent process, so
+            // the token is only relevant for HTTP clients
+            if !stdio {
+                if let Some(token) = auth_manager.get_token().await {
+                    info!("Authentication token: {}", token);
+                    info!("Set CLAUDE_UTILS_TOKEN={} in your environment", token);
+                }
+            }
+
+            // The watcher publishes here; the processor, SSE clients and MCP
+            // sessions subscribe
+            let events = EventBus::new();
+
+            // Without --watch the clipboard is left alone until an MCP client
+            // subscribes to it or waits for a change
+            let poll_interval = poll_interval.unwrap_or(config.watch.poll_interval_ms);
+            let mut watcher = ClipboardWatcher::new(
+                clipboard.clone(),
+                Duration::from_millis(poll_interval),
+                events.clone(),
+            )
+            .with_idle_interval(Duration::from_millis(config.watch.idle_poll_interval_ms))
+            .with_filter(WatchFilter::new(config.watch.filter)?);
+
+            let plugins = PluginHost::load(config.plugins)?;
+            if !plugins.is_empty() {
+                info!("Loaded plugins: {:?}", plugins);
+                watcher = watcher.with_plugins(plugins.clone());
             }
 
-            // Start clipboard watcher if enabled
+            if watch_primary {
+                watcher = watcher.watch_selection(Selection::Primary);
+            }
+            if !watch {
+                watcher = watcher.on_demand();
+            }
+
+            // MCP sessions and SSE clients hear about copies from here; in
+            // watch mode only once the processor has staged them
+            let mut server_events = events.clone();
+
+            // Start the processor if watch mode is enabled
             if watch {
                 info!("Clipboard watching enabled");
 
@@ -164,33 +349,33 @@ async fn main() -> Result<()> {
                             .unwrap_or_else(|| PathBuf::from("."))
                             .join("Desktop")
                     }),
+                    link_template: config.watch.link_template,
+                    delivery: delivery.unwrap_or(config.watch.delivery),
                     enable_dual_format: !no_dual_format,
                     enable_notifications: !no_notifications,
+                    rules: RuleSet::new(
+                        config.watch.rules.unwrap_or_else(RuleSet::default_configs),
+                    )?,
+                    hooks: config.watch.hooks,
+                    max_concurrent_hooks: config.watch.max_concurrent_hooks,
                     ..Default::default()
                 };
 
-                let (watcher, event_rx) = ClipboardWatcher::new(
-                    clipboard.clone(),
-                    Duration::from_millis(500), // Poll every 500ms
-                );
-
+                server_events = EventBus::new();
                 let processor = ClipboardProcessor::new(
                     processor_config,
                     file_manager.clone(),
                     clipboard.clone(),
-                );
-
-                // Spawn watcher task
-                tokio::spawn(async move {
-                    watcher.start_watching().await;
-                });
+                )
+                .with_output(server_events.clone());
+                // Every image must be staged, so the watcher waits for it
+                let processor_events = events.subscribe("processor", 100, LagPolicy::Block);
 
                 // Spawn processor task
                 tokio::spawn(async move {
-                    processor.start_processing(event_rx).await;
+                    processor.start_processing(processor_events).await;
                 });
 
-                info!("Clipboard watcher started");
                 info!("Images will be saved to Desktop with dual-format clipboard");
             }
 
@@ -202,14 +387,27 @@ async fn main() -> Result<()> {
                 port,
                 host.clone(),
             )
-            .await?;
+            .await?
+            .with_events(server_events)
+            .with_watch_control(watcher.control())
+            .with_plugins(plugins)
+            .with_writes(write);
+
+            // Started after the server subscribes, so it sees the first event
+            tokio::spawn(async move {
+                watcher.start_watching().await;
+            });
 
-            info!("Starting MCP server on {}:{}", host, port);
             if write {
                 info!("Write operations enabled");
             }
 
-            server.run().await?;
+            if stdio {
+                server.run_stdio().await?;
+            } else {
+                info!("Starting MCP server on {}:{}", host, port);
+                server.run().await?;
+            }
         }
 
         Commands::Token => {
@@ -225,11 +423,10 @@ async fn main() -> Result<()> {
 
         Commands::Config { output } => {
             let config = serde_json::json!({
-                "claude-utils": {
-                    "command": "claude-utils",
-                    "args": ["start"],
-                    "env": {
-                        "CLAUDE_UTILS_TOKEN": "${CLAUDE_UTILS_TOKEN}"
+                "mcpServers": {
+                    "claude-utils": {
+                        "command": "claude-utils",
+                        "args": ["start", "--stdio"]
                     }
                 }
             });
@@ -244,21 +441,27 @@ async fn main() -> Result<()> {
             }
         }
 
-        Commands::Clip { action } => {
-            let clipboard = ClipboardManager::new()?;
+        Commands::Clip { action, port } => {
+            let clipboard = open_clipboard(cli.backend, cli.clipboard_file.clone())?;
 
             match action {
-                ClipAction::Get { format } => {
-                    let content = clipboard.get_content()?;
+                ClipAction::Get { format, selection } => {
+                    let content = clipboard.get_content_from(selection)?;
 
                     match format.as_str() {
                         "json" => {
                             println!("{}", serde_json::to_string_pretty(&content)?);
                         }
-                        "text" => match &content.content {
-                            claude_utils::clipboard::ClipboardContent::Text { data, .. } => {
+                        "text" => match content.content.into_markdown() {
+                            ClipboardContent::Text { data, .. }
+                            | ClipboardContent::Markdown { data, .. } => {
                                 println!("{data}");
                             }
+                            ClipboardContent::Files { files } => {
+                                for file in files {
+                                    println!("{}", file.path);
+                                }
+                            }
                             _ => {
                                 println!("[Image in clipboard]");
                             }
@@ -270,27 +473,175 @@ async fn main() -> Result<()> {
                     }
                 }
 
-                ClipAction::Paste => {
-                    let content = clipboard.get_content()?;
+                ClipAction::Formats { selection, format } => {
+                    let formats = clipboard.list_formats(selection)?;
 
-                    match &content.content {
-                        claude_utils::clipboard::ClipboardContent::Text { data, .. } => {
+                    match format.as_str() {
+                        "json" => {
+                            println!("{}", serde_json::to_string_pretty(&formats)?);
+                        }
+                        "text" => {
+                            for info in formats {
+                                let size = info
+                                    .size
+                                    .map_or_else(|| "-".to_string(), |size| size.to_string());
+                                println!("{size:>10}  {}", info.mime);
+                            }
+                        }
+                        _ => {
+                            error!("Unknown format: {}", format);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+
+                ClipAction::Paste { selection } => {
+                    let content = clipboard.get_content_from(selection)?;
+
+                    match &content.content.into_markdown() {
+                        claude_utils::clipboard::ClipboardContent::Text { data, .. }
+                        | claude_utils::clipboard::ClipboardContent::Markdown { data, .. }
+                        | claude_utils::clipboard::ClipboardContent::Html { data, .. } => {
                             print!("{data}");
                         }
+                        claude_utils::clipboard::ClipboardContent::Files { files } => {
+                            let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+                            print!("{}", paths.join("\n"));
+                        }
                         claude_utils::clipboard::ClipboardContent::ImagePng { .. }
                         | claude_utils::clipboard::ClipboardContent::ImageJpeg { .. } => {
                             // Stage image and output path
                             let file_manager =
                                 FileManager::new(FileManagerConfig::default()).await?;
-                            let image_data = clipboard.get_raw_image()?;
+                            let image_data = clipboard.get_raw_image_from(selection)?;
                             let staged = file_manager.stage_image(&image_data, "png").await?;
                             print!("{}", staged.path.display());
                         }
+                        claude_utils::clipboard::ClipboardContent::Target { data, .. } => {
+                            print!("{}", data.as_deref().unwrap_or_default());
+                        }
                     }
                 }
+
+                ClipAction::Set {
+                    text,
+                    file,
+                    selection,
+                } => {
+                    let content = match (text, file) {
+                        (Some(data), _) => ClipboardContent::Text {
+                            data,
+                            truncated: None,
+                        },
+                        (None, Some(path)) => {
+                            let mime = mime_guess::from_path(&path).first_or_text_plain();
+
+                            if mime.type_() == mime_guess::mime::IMAGE {
+                                ClipboardContent::Target {
+                                    mime: mime.essence_str().to_string(),
+                                    data: None,
+                                    encoding: None,
+                                    // Absolute, as the daemon may pick it up
+                                    file: Some(
+                                        std::fs::canonicalize(&path)?.to_string_lossy().to_string(),
+                                    ),
+                                    size: 0,
+                                }
+                            } else {
+                                ClipboardContent::Text {
+                                    data: std::fs::read_to_string(&path)?,
+                                    truncated: None,
+                                }
+                            }
+                        }
+                        (None, None) => ClipboardContent::Text {
+                            data: std::io::read_to_string(std::io::stdin())?,
+                            truncated: None,
+                        },
+                    };
+
+                    // X11 and Wayland drop the contents when their owner
+                    // exits, so leave them with a process that stays around
+                    if cfg!(target_os = "linux") && is_desktop_backend(clipboard.backend_name()) {
+                        hand_off(selection, &content, port).await?;
+                    } else {
+                        clipboard.set_content_to(selection, &content)?;
+                    }
+                }
+            }
+        }
+
+        Commands::Watch { action, port } => {
+            let Some(daemon) = DaemonClient::connect(DEFAULT_HOST, port).await else {
+                error!("No claude-utils daemon is running on port {}", port);
+                std::process::exit(1);
+            };
+
+            let tool = match action {
+                WatchAction::Pause => "watch.pause",
+                WatchAction::Resume => "watch.resume",
+                WatchAction::Status => "watch.status",
+            };
+            let result = daemon.call_tool(tool, serde_json::json!({})).await?;
+            println!(
+                "{}",
+                result["content"][0]["text"].as_str().unwrap_or_default()
+            );
+        }
+
+        Commands::HoldSelection { selection } => {
+            let content: ClipboardContent = serde_json::from_reader(std::io::stdin())?;
+
+            let clipboard =
+                ClipboardManager::with_backend(SystemClipboard::new()?.wait_until_replaced());
+            clipboard.set_content_to(selection, &content)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Leaves a write with the running daemon, or with a detached
+/// `hold-selection` child when there is none.
+async fn hand_off(selection: Selection, content: &ClipboardContent, port: u16) -> Result<()> {
+    if let Some(daemon) = DaemonClient::connect(DEFAULT_HOST, port).await {
+        if is_desktop_backend(daemon.backend()) {
+            match daemon.set_content(selection, content).await {
+                Ok(()) => return Ok(()),
+                Err(e) => info!("Daemon did not take the write ({}), holding it here", e),
             }
         }
     }
 
+    spawn_selection_holder(selection, content)
+}
+
+/// Backends whose writes live in the X11/Wayland selection.
+fn is_desktop_backend(name: &str) -> bool {
+    matches!(name, "system" | "wayland")
+}
+
+fn spawn_selection_holder(selection: Selection, content: &ClipboardContent) -> Result<()> {
+    use std::process::{Command, Stdio};
+
+    let mut command = Command::new(std::env::current_exe()?);
+    command
+        .args(["hold-selection", "--selection", selection.as_str()])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    // Keep Ctrl-C and terminal hangups aimed at us away from the holder
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    let mut child = command.spawn()?;
+    if let Some(stdin) = child.stdin.take() {
+        serde_json::to_writer(stdin, content)?;
+    }
+
     Ok(())
 }
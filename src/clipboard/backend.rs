@@ -0,0 +1,219 @@
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use tokio::sync::mpsc;
+
+use crate::{ClaudeUtilsError, Result};
+
+pub const MIME_TEXT: &str = "text/plain";
+pub const MIME_TEXT_UTF8: &str = "text/plain;charset=utf-8";
+pub const MIME_HTML: &str = "text/html";
+pub const MIME_URI_LIST: &str = "text/uri-list";
+pub const MIME_PNG: &str = "image/png";
+
+/// Which selection an operation targets. Only X11 and Wayland have more
+/// than the regular clipboard.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Selection {
+    #[default]
+    Clipboard,
+    Primary,
+    Secondary,
+}
+
+impl Selection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Selection::Clipboard => "clipboard",
+            Selection::Primary => "primary",
+            Selection::Secondary => "secondary",
+        }
+    }
+
+    pub fn is_clipboard(&self) -> bool {
+        *self == Selection::Clipboard
+    }
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Selection {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "clipboard" => Ok(Selection::Clipboard),
+            "primary" => Ok(Selection::Primary),
+            "secondary" => Ok(Selection::Secondary),
+            other => Err(format!(
+                "unknown selection '{other}' (expected clipboard, primary or secondary)"
+            )),
+        }
+    }
+}
+
+/// Uncompressed RGBA8 pixels, as exchanged with clipboard backends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawImage {
+    pub width: usize,
+    pub height: usize,
+    pub bytes: Vec<u8>,
+}
+
+/// A selection got a new owner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnerChange {
+    pub selection: Selection,
+    /// The MIME types the owner announced, where the notification carries
+    /// them (Wayland data-control); otherwise read them with `formats`
+    pub mime_types: Option<Vec<String>>,
+}
+
+impl From<Selection> for OwnerChange {
+    fn from(selection: Selection) -> Self {
+        Self {
+            selection,
+            mime_types: None,
+        }
+    }
+}
+
+/// Storage that a `ClipboardManager` reads from and writes to.
+///
+/// The system clipboard is one implementation; others let the daemon run
+/// somewhere without a display, or let tests drive it directly.
+pub trait ClipboardBackend: Send {
+    /// Short name shown in logs and `/health`
+    fn name(&self) -> &'static str;
+
+    /// Whether `selection` exists here. Everything has the clipboard.
+    fn supports_selection(&self, selection: Selection) -> bool {
+        selection.is_clipboard()
+    }
+
+    fn get_text(&mut self, selection: Selection) -> Result<String>;
+
+    fn set_text(&mut self, selection: Selection, text: &str) -> Result<()>;
+
+    fn get_image(&mut self, selection: Selection) -> Result<RawImage>;
+
+    fn set_image(&mut self, selection: Selection, image: &RawImage) -> Result<()>;
+
+    /// MIME targets currently offered, in the owner's order.
+    fn formats(&mut self, selection: Selection) -> Result<Vec<String>>;
+
+    /// Reads the raw bytes of a single MIME target.
+    fn get_format(&mut self, selection: Selection, mime: &str) -> Result<Vec<u8>>;
+
+    /// Reads a target exactly as the owner offers it, without the
+    /// conversions `get_format` may apply to the well-known ones.
+    fn read_target(&mut self, selection: Selection, mime: &str) -> Result<Vec<u8>> {
+        self.get_format(selection, mime)
+    }
+
+    /// The size of a target, if it is known without transferring it.
+    fn format_size(&mut self, _selection: Selection, _mime: &str) -> Option<usize> {
+        None
+    }
+
+    /// Replaces the selection with all `formats` offered together.
+    fn set_formats(&mut self, selection: Selection, formats: &[(String, Vec<u8>)]) -> Result<()>;
+
+    /// Identifies the current contents without encoding anything, hashing
+    /// the raw bytes of the representation `ClipboardManager::get_content`
+    /// picks. Equal fingerprints mean nothing changed.
+    fn fingerprint(&mut self, selection: Selection) -> Result<u64> {
+        if let Ok(image) = self.get_image(selection) {
+            return Ok(hash_image(&image));
+        }
+
+        if let Ok(list) = self.get_format(selection, MIME_URI_LIST) {
+            if lists_files(&list) {
+                return Ok(hash_format(MIME_URI_LIST, &list));
+            }
+        }
+
+        if let Ok(html) = self.get_format(selection, MIME_HTML) {
+            return Ok(hash_format(MIME_HTML, &html));
+        }
+
+        Ok(hash_format(MIME_TEXT, self.get_text(selection)?.as_bytes()))
+    }
+
+    /// Yields a change each time one of `selections` gets a new owner, for
+    /// backends that can be told rather than polled. `None` means poll.
+    fn owner_changes(
+        &mut self,
+        _selections: &[Selection],
+    ) -> Option<mpsc::UnboundedReceiver<OwnerChange>> {
+        None
+    }
+}
+
+/// The fingerprint of contents whose representation is `image`.
+pub(crate) fn hash_image(image: &RawImage) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (MIME_PNG, image.width, image.height, &image.bytes).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The fingerprint of contents whose representation is `bytes` of `mime`.
+pub(crate) fn hash_format(mime: &str, bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (mime, bytes).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Whether a `text/uri-list` names any files; copied files are only
+/// reported as such when it does.
+pub(crate) fn lists_files(list: &[u8]) -> bool {
+    !super::files::parse_uri_list(&String::from_utf8_lossy(list)).is_empty()
+}
+
+/// Whether `mime` names a plain-text target (including legacy X11 names).
+pub fn is_text_mime(mime: &str) -> bool {
+    let base = mime.split(';').next().unwrap_or(mime).trim();
+    matches!(base, "text/plain" | "UTF8_STRING" | "STRING" | "TEXT")
+}
+
+pub fn encode_png(image: &RawImage) -> Result<Vec<u8>> {
+    let img =
+        image::RgbaImage::from_raw(image.width as u32, image.height as u32, image.bytes.clone())
+            .ok_or_else(|| {
+                ClaudeUtilsError::ImageProcessing(image::ImageError::Limits(
+                    image::error::LimitError::from_kind(
+                        image::error::LimitErrorKind::DimensionError,
+                    ),
+                ))
+            })?;
+
+    let mut png_bytes = Vec::new();
+    img.write_to(
+        &mut std::io::Cursor::new(&mut png_bytes),
+        image::ImageFormat::Png,
+    )?;
+
+    Ok(png_bytes)
+}
+
+/// Decodes any format the `image` crate understands into RGBA pixels.
+pub fn decode_image(bytes: &[u8]) -> Result<RawImage> {
+    let img = image::load_from_memory(bytes)?.to_rgba8();
+
+    Ok(RawImage {
+        width: img.width() as usize,
+        height: img.height() as usize,
+        bytes: img.into_raw(),
+    })
+}
+
+pub(crate) fn unsupported_format(backend: &str, mime: &str) -> ClaudeUtilsError {
+    ClaudeUtilsError::Clipboard(format!("{backend} backend cannot provide {mime}"))
+}
@@ -0,0 +1,238 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tokio::fs;
+use tracing::warn;
+
+use crate::{ClaudeUtilsError, Result};
+
+/// Gives up on finding a free name after this many numbered attempts.
+const MAX_COLLISIONS: usize = 1000;
+
+/// How a staged file is put in the link directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Delivery {
+    /// Points at the staged file, which is kept while the link exists
+    #[default]
+    Symlink,
+    /// Shares the staged file's data; becomes a copy across file systems
+    Hardlink,
+    /// An independent copy, for sandboxes that follow no links
+    Copy,
+}
+
+impl Delivery {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Delivery::Symlink => "symlink",
+            Delivery::Hardlink => "hardlink",
+            Delivery::Copy => "copy",
+        }
+    }
+}
+
+impl fmt::Display for Delivery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Delivery {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "symlink" => Ok(Delivery::Symlink),
+            "hardlink" => Ok(Delivery::Hardlink),
+            "copy" => Ok(Delivery::Copy),
+            other => Err(format!(
+                "Unknown delivery mode: {other} (expected symlink, hardlink or copy)"
+            )),
+        }
+    }
+}
+
+/// The name of each delivered file, after the prefix and before the
+/// extension. Placeholders:
+///
+/// - `{timestamp}`: local time as `%Y%m%d-%H%M%S`
+/// - `{hash}`: the first 8 hex digits of the content's SHA-256
+/// - `{counter}`: 1 for the first file delivered since start, then 2, ...
+/// - `{slug}`: the first words of copied text, or `image-WxH`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct LinkTemplate(String);
+
+const PLACEHOLDERS: &[&str] = &["timestamp", "hash", "counter", "slug"];
+
+impl LinkTemplate {
+    pub fn new(template: &str) -> Result<Self> {
+        let invalid = |message: String| {
+            ClaudeUtilsError::Config(format!("Link template {template:?}: {message}"))
+        };
+
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| invalid("unclosed `{`".to_string()))?;
+            let name = &rest[start + 1..start + end];
+            if !PLACEHOLDERS.contains(&name) {
+                return Err(invalid(format!("unknown placeholder {{{name}}}")));
+            }
+            rest = &rest[start + end + 1..];
+        }
+        if template.contains(['/', '\\']) {
+            return Err(invalid("must not contain a path separator".to_string()));
+        }
+
+        Ok(Self(template.to_string()))
+    }
+
+    pub fn render(&self, fields: &LinkFields) -> String {
+        self.0
+            .replace(
+                "{timestamp}",
+                &Local::now().format("%Y%m%d-%H%M%S").to_string(),
+            )
+            .replace("{hash}", fields.hash.get(..8).unwrap_or(&fields.hash))
+            .replace("{counter}", &fields.counter.to_string())
+            .replace("{slug}", &fields.slug)
+    }
+}
+
+impl Default for LinkTemplate {
+    fn default() -> Self {
+        Self("{timestamp}".to_string())
+    }
+}
+
+impl TryFrom<String> for LinkTemplate {
+    type Error = ClaudeUtilsError;
+
+    fn try_from(template: String) -> Result<Self> {
+        Self::new(&template)
+    }
+}
+
+impl From<LinkTemplate> for String {
+    fn from(template: LinkTemplate) -> Self {
+        template.0
+    }
+}
+
+/// What a `LinkTemplate` is filled in from.
+#[derive(Debug, Clone)]
+pub struct LinkFields {
+    pub hash: String,
+    pub counter: u64,
+    pub slug: String,
+}
+
+/// Lowercase words of `text` joined by `-`, at most 32 characters.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        let word = word.to_lowercase();
+        let separator = usize::from(!slug.is_empty());
+        if slug.chars().count() + separator + word.chars().count() > 32 {
+            if slug.is_empty() {
+                slug = word.chars().take(32).collect();
+            }
+            break;
+        }
+        if separator == 1 {
+            slug.push('-');
+        }
+        slug.push_str(&word);
+    }
+
+    slug
+}
+
+/// Puts `target` in `dir` as `name.extension`, numbering the name
+/// (`name-2.extension`, ...) until it is free. Returns the path used.
+pub async fn deliver(
+    target: &Path,
+    dir: &Path,
+    name: &str,
+    extension: &str,
+    delivery: Delivery,
+) -> Result<PathBuf> {
+    for attempt in 1..=MAX_COLLISIONS {
+        let filename = if attempt == 1 {
+            format!("{name}.{extension}")
+        } else {
+            format!("{name}-{attempt}.{extension}")
+        };
+        let path = dir.join(filename);
+
+        match place(target, &path, delivery).await {
+            Ok(()) => return Ok(path),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Err(ClaudeUtilsError::FileOperation(std::io::Error::new(
+        ErrorKind::AlreadyExists,
+        format!("No free name for {name}.{extension} in {}", dir.display()),
+    )))
+}
+
+/// Puts `target` at `path`, replacing whatever is there.
+pub async fn replace(target: &Path, path: &Path, delivery: Delivery) -> Result<()> {
+    match fs::remove_file(path).await {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+
+    Ok(place(target, path, delivery).await?)
+}
+
+/// Creates `path`, failing with `AlreadyExists` if it is taken.
+async fn place(target: &Path, path: &Path, delivery: Delivery) -> std::io::Result<()> {
+    match delivery {
+        Delivery::Symlink => symlink(target, path).await,
+        Delivery::Hardlink => match fs::hard_link(target, path).await {
+            Err(e) if e.kind() != ErrorKind::AlreadyExists => {
+                // Most likely another file system
+                warn!("Cannot hard link {}, copying: {}", path.display(), e);
+                copy_new(target, path).await
+            }
+            result => result,
+        },
+        Delivery::Copy => copy_new(target, path).await,
+    }
+}
+
+async fn symlink(target: &Path, path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        fs::symlink(target, path).await
+    }
+
+    #[cfg(windows)]
+    {
+        fs::symlink_file(target, path).await
+    }
+}
+
+async fn copy_new(target: &Path, path: &Path) -> std::io::Result<()> {
+    let mut source = fs::File::open(target).await?;
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .await?;
+    tokio::io::copy(&mut source, &mut file).await?;
+    Ok(())
+}
@@ -0,0 +1,192 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, warn};
+
+use super::watcher::ClipboardEvent;
+
+/// What happens to a subscriber that falls behind the watcher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LagPolicy {
+    /// Wait for room, slowing the watcher down; for consumers that must
+    /// see every event, like the processor
+    Block,
+    /// Discard the oldest queued events to make room; for live views
+    /// such as SSE, where only recent changes matter
+    DropOldest,
+    /// Discard new events until there is room, keeping the backlog
+    DropNewest,
+    /// Detach the subscriber, e.g. a webhook that stopped answering
+    Disconnect,
+}
+
+enum Outbox {
+    Queue(mpsc::Sender<ClipboardEvent>),
+    Ring(broadcast::Sender<ClipboardEvent>),
+}
+
+struct Subscriber {
+    id: u64,
+    name: String,
+    policy: LagPolicy,
+    outbox: Outbox,
+}
+
+type Subscribers = Mutex<Vec<Arc<Subscriber>>>;
+
+/// Fans every clipboard event out to any number of subscribers, each with
+/// its own queue and `LagPolicy`. Clones publish to the same subscribers.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Subscribers>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attaches a subscriber with room for `capacity` queued events. It is
+    /// detached again when the returned `Subscription` is dropped.
+    pub fn subscribe(
+        &self,
+        name: impl Into<String>,
+        capacity: usize,
+        policy: LagPolicy,
+    ) -> Subscription {
+        let capacity = capacity.max(1);
+        let (outbox, inbox) = match policy {
+            LagPolicy::DropOldest => {
+                let (tx, rx) = broadcast::channel(capacity);
+                (Outbox::Ring(tx), Inbox::Ring(rx))
+            }
+            _ => {
+                let (tx, rx) = mpsc::channel(capacity);
+                (Outbox::Queue(tx), Inbox::Queue(rx))
+            }
+        };
+
+        let subscriber = Arc::new(Subscriber {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            name: name.into(),
+            policy,
+            outbox,
+        });
+        debug!("Event subscriber attached: {}", subscriber.name);
+
+        let id = subscriber.id;
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(subscriber);
+        }
+
+        Subscription {
+            id,
+            inbox,
+            bus: Arc::downgrade(&self.subscribers),
+        }
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.lock().map(|s| s.len()).unwrap_or(0)
+    }
+
+    /// Hands `event` to every subscriber according to its policy.
+    pub async fn publish(&self, event: ClipboardEvent) {
+        // Sending may wait, so work from a copy of the list
+        let subscribers = match self.subscribers.lock() {
+            Ok(subscribers) => subscribers.clone(),
+            Err(_) => return,
+        };
+
+        for subscriber in subscribers {
+            let delivered = match (&subscriber.outbox, subscriber.policy) {
+                (Outbox::Ring(tx), _) => tx.send(event.clone()).is_ok(),
+                (Outbox::Queue(tx), LagPolicy::Block) => tx.send(event.clone()).await.is_ok(),
+                (Outbox::Queue(tx), policy) => match tx.try_send(event.clone()) {
+                    Ok(()) => true,
+                    Err(mpsc::error::TrySendError::Full(_)) if policy == LagPolicy::DropNewest => {
+                        warn!(
+                            "Event subscriber {} is full, dropping event",
+                            subscriber.name
+                        );
+                        true
+                    }
+                    Err(mpsc::error::TrySendError::Full(_)) => {
+                        warn!(
+                            "Event subscriber {} fell behind, detaching it",
+                            subscriber.name
+                        );
+                        false
+                    }
+                    Err(mpsc::error::TrySendError::Closed(_)) => false,
+                },
+            };
+
+            if !delivered {
+                detach(&self.subscribers, subscriber.id);
+            }
+        }
+    }
+}
+
+fn detach(subscribers: &Subscribers, id: u64) {
+    if let Ok(mut subscribers) = subscribers.lock() {
+        if let Some(index) = subscribers.iter().position(|s| s.id == id) {
+            debug!("Event subscriber detached: {}", subscribers[index].name);
+            subscribers.remove(index);
+        }
+    }
+}
+
+enum Inbox {
+    Queue(mpsc::Receiver<ClipboardEvent>),
+    Ring(broadcast::Receiver<ClipboardEvent>),
+}
+
+/// One subscriber's view of the bus.
+pub struct Subscription {
+    id: u64,
+    inbox: Inbox,
+    bus: Weak<Subscribers>,
+}
+
+impl Subscription {
+    /// The next event, or `None` once the bus is gone or has detached us.
+    pub async fn recv(&mut self) -> Option<ClipboardEvent> {
+        match &mut self.inbox {
+            Inbox::Queue(rx) => rx.recv().await,
+            Inbox::Ring(rx) => loop {
+                match rx.recv().await {
+                    Ok(event) => return Some(event),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        debug!("Event subscriber skipped {} old events", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            },
+        }
+    }
+
+    /// An event that is already queued, without waiting.
+    pub fn try_recv(&mut self) -> Option<ClipboardEvent> {
+        match &mut self.inbox {
+            Inbox::Queue(rx) => rx.try_recv().ok(),
+            Inbox::Ring(rx) => loop {
+                match rx.try_recv() {
+                    Ok(event) => return Some(event),
+                    Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+                    Err(_) => return None,
+                }
+            },
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(subscribers) = self.bus.upgrade() {
+            detach(&subscribers, self.id);
+        }
+    }
+}
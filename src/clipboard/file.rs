@@ -0,0 +1,223 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::backend::{
+    decode_image, encode_png, is_text_mime, unsupported_format, ClipboardBackend, RawImage,
+    Selection, MIME_PNG, MIME_TEXT,
+};
+use crate::{ClaudeUtilsError, Result};
+
+pub const CLIPBOARD_FILE_NAME: &str = "clipboard.json";
+
+/// On-disk layout of the virtual clipboard.
+///
+/// ```json
+/// {"updated_at": "2025-01-01T00:00:00Z",
+///  "formats": [{"mime": "text/plain", "data": "<base64>"}],
+///  "primary": [{"mime": "text/plain", "data": "<base64>"}]}
+/// ```
+///
+/// `formats` is the clipboard; `primary` and `secondary` are only present
+/// once something has been written to them. Any process may replace the
+/// file, ideally by writing a sibling file and renaming it over this one so
+/// readers never see a partial write.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoredClipboard {
+    #[serde(default)]
+    updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    formats: Vec<StoredFormat>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    primary: Vec<StoredFormat>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    secondary: Vec<StoredFormat>,
+}
+
+impl StoredClipboard {
+    fn selection_mut(&mut self, selection: Selection) -> &mut Vec<StoredFormat> {
+        match selection {
+            Selection::Clipboard => &mut self.formats,
+            Selection::Primary => &mut self.primary,
+            Selection::Secondary => &mut self.secondary,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredFormat {
+    mime: String,
+    data: String, // base64 encoded
+}
+
+/// A clipboard kept in a file, for hosts without X11, Wayland or a desktop
+/// session (headless VMs, SSH sessions, containers).
+pub struct FileClipboard {
+    path: PathBuf,
+}
+
+impl FileClipboard {
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        Ok(Self { path })
+    }
+
+    /// The clipboard file under the state directory.
+    pub fn default_path() -> PathBuf {
+        crate::state_dir().join(CLIPBOARD_FILE_NAME)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn load(&self) -> Result<StoredClipboard> {
+        let raw = match fs::read(&self.path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(StoredClipboard::default())
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(serde_json::from_slice(&raw)?)
+    }
+
+    fn read(&self, selection: Selection) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut file = self.load()?;
+
+        std::mem::take(file.selection_mut(selection))
+            .into_iter()
+            .map(|format| {
+                let bytes = BASE64.decode(&format.data).map_err(|e| {
+                    ClaudeUtilsError::Clipboard(format!("Base64 decode error: {e}"))
+                })?;
+                Ok((format.mime, bytes))
+            })
+            .collect()
+    }
+
+    fn write(&self, selection: Selection, formats: &[(String, Vec<u8>)]) -> Result<()> {
+        // The other selections are carried over untouched, so a file that
+        // cannot be read is left alone rather than replaced
+        let mut file = self.load().map_err(|e| {
+            ClaudeUtilsError::Clipboard(format!("Cannot read {}: {e}", self.path.display()))
+        })?;
+        file.updated_at = Some(chrono::Utc::now());
+        *file.selection_mut(selection) = formats
+            .iter()
+            .map(|(mime, bytes)| StoredFormat {
+                mime: mime.clone(),
+                data: BASE64.encode(bytes),
+            })
+            .collect();
+
+        // Write-then-rename so concurrent readers never see half a file
+        let tmp_path = self
+            .path
+            .with_extension(format!("json.{}.tmp", std::process::id()));
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        // Private from the moment it exists, whatever the umask
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        // Left over from a write that never finished
+        let _ = fs::remove_file(&tmp_path);
+        let mut tmp = options.open(&tmp_path)?;
+        tmp.write_all(&serde_json::to_vec(&file)?)?;
+        drop(tmp);
+
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+
+    fn find(
+        &self,
+        selection: Selection,
+        matches: impl Fn(&str) -> bool,
+    ) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .read(selection)?
+            .into_iter()
+            .find(|(mime, _)| matches(mime))
+            .map(|(_, bytes)| bytes))
+    }
+}
+
+impl ClipboardBackend for FileClipboard {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn supports_selection(&self, _selection: Selection) -> bool {
+        true
+    }
+
+    fn get_text(&mut self, selection: Selection) -> Result<String> {
+        match self.find(selection, is_text_mime)? {
+            Some(bytes) => {
+                String::from_utf8(bytes).map_err(|e| ClaudeUtilsError::Clipboard(e.to_string()))
+            }
+            None => Err(ClaudeUtilsError::Clipboard(
+                "No text in clipboard".to_string(),
+            )),
+        }
+    }
+
+    fn set_text(&mut self, selection: Selection, text: &str) -> Result<()> {
+        self.write(
+            selection,
+            &[(MIME_TEXT.to_string(), text.as_bytes().to_vec())],
+        )
+    }
+
+    fn get_image(&mut self, selection: Selection) -> Result<RawImage> {
+        match self.find(selection, |mime| mime.starts_with("image/"))? {
+            Some(bytes) => decode_image(&bytes),
+            None => Err(ClaudeUtilsError::Clipboard(
+                "No image in clipboard".to_string(),
+            )),
+        }
+    }
+
+    fn set_image(&mut self, selection: Selection, image: &RawImage) -> Result<()> {
+        self.write(selection, &[(MIME_PNG.to_string(), encode_png(image)?)])
+    }
+
+    fn formats(&mut self, selection: Selection) -> Result<Vec<String>> {
+        Ok(self
+            .read(selection)?
+            .into_iter()
+            .map(|(mime, _)| mime)
+            .collect())
+    }
+
+    fn get_format(&mut self, selection: Selection, mime: &str) -> Result<Vec<u8>> {
+        let wanted_text = is_text_mime(mime);
+
+        self.find(selection, |offered| {
+            offered == mime || (wanted_text && is_text_mime(offered))
+        })?
+        .ok_or_else(|| unsupported_format("file", mime))
+    }
+
+    fn format_size(&mut self, selection: Selection, mime: &str) -> Option<usize> {
+        self.get_format(selection, mime)
+            .ok()
+            .map(|bytes| bytes.len())
+    }
+
+    fn set_formats(&mut self, selection: Selection, formats: &[(String, Vec<u8>)]) -> Result<()> {
+        self.write(selection, formats)
+    }
+}
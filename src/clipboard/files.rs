@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// One file copied from a file manager.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileEntry {
+    pub path: String,
+    pub size: u64,
+    pub mime_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>, // contents, for small text files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>, // staged copy, for images
+}
+
+impl FileEntry {
+    pub fn from_path(path: &Path) -> Self {
+        let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        let mime_type = mime_guess::from_path(path)
+            .first_or_octet_stream()
+            .essence_str()
+            .to_string();
+
+        Self {
+            path: path.to_string_lossy().to_string(),
+            size,
+            mime_type,
+            data: None,
+            file: None,
+        }
+    }
+
+    pub fn is_text(&self) -> bool {
+        self.mime_type.starts_with("text/")
+            || matches!(
+                self.mime_type.as_str(),
+                "application/json" | "application/xml" | "application/toml"
+            )
+    }
+
+    pub fn is_image(&self) -> bool {
+        self.mime_type.starts_with("image/")
+    }
+}
+
+/// Parses a `text/uri-list` (RFC 2483) into local paths.
+///
+/// Comment lines and URIs that are not `file://` are skipped.
+pub fn parse_uri_list(list: &str) -> Vec<PathBuf> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| Url::parse(line).ok())
+        .filter_map(|url| url.to_file_path().ok())
+        .collect()
+}
+
+pub fn to_uri_list<P: AsRef<Path>>(paths: &[P]) -> String {
+    paths
+        .iter()
+        .filter_map(|path| Url::from_file_path(path).ok())
+        .map(|url| format!("{url}\r\n"))
+        .collect()
+}
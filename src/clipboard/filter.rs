@@ -0,0 +1,124 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::ClipboardSnapshot;
+use crate::{ClaudeUtilsError, Result};
+
+/// Targets a password manager or similar adds to say the content must not
+/// be recorded. KeePassXC and KDE apps set `x-kde-passwordManagerHint`.
+///
+/// Only X11 and Wayland list every target the owner offers. On macOS and
+/// Windows just the formats arboard reads can be seen, so no hint ever is
+/// and nothing is filtered on hints there.
+pub const DEFAULT_HINT_TARGETS: &[&str] =
+    &["x-kde-passwordManagerHint", "CLIPBOARD_MANAGER_IGNORE"];
+
+/// Which copies watch mode leaves alone, as read from the `[watch.filter]`
+/// table of the config file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterConfig {
+    /// Skip content whose owner offers any of `hint_targets`
+    pub honor_hints: bool,
+    pub hint_targets: Vec<String>,
+    /// MIME types to skip; `image/*` matches every image
+    pub ignore_types: Vec<String>,
+    /// Bounds in bytes on the content as captured
+    pub min_size: Option<usize>,
+    pub max_size: Option<usize>,
+    /// Regular expressions; text matching any of them is skipped
+    pub ignore_patterns: Vec<String>,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self {
+            honor_hints: true,
+            hint_targets: DEFAULT_HINT_TARGETS.iter().map(|t| t.to_string()).collect(),
+            ignore_types: Vec::new(),
+            min_size: None,
+            max_size: None,
+            ignore_patterns: Vec::new(),
+        }
+    }
+}
+
+/// Decides which copies the watcher withholds. Withheld content is never
+/// published, so it reaches neither the processor nor staging, and the
+/// watcher logs only why it was skipped.
+#[derive(Debug, Clone, Default)]
+pub struct WatchFilter {
+    config: FilterConfig,
+    patterns: Vec<Regex>,
+}
+
+impl WatchFilter {
+    pub fn new(config: FilterConfig) -> Result<Self> {
+        let patterns = config
+            .ignore_patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| {
+                    ClaudeUtilsError::Config(format!("Invalid ignore pattern {pattern:?}: {e}"))
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { config, patterns })
+    }
+
+    /// Whether copies are checked for password-manager hints at all.
+    pub fn honors_hints(&self) -> bool {
+        self.config.honor_hints
+    }
+
+    /// Whether the owner marked its content as secret. Checked on the
+    /// offered targets alone.
+    pub fn is_sensitive(&self, mime_types: &[String]) -> bool {
+        self.config.honor_hints
+            && mime_types
+                .iter()
+                .any(|mime| self.config.hint_targets.iter().any(|hint| hint == mime))
+    }
+
+    /// Why `snapshot` should be skipped, if it should.
+    pub fn rejects(&self, snapshot: &ClipboardSnapshot) -> Option<&'static str> {
+        let (mime, bytes) = snapshot.formats().first()?;
+
+        if self
+            .config
+            .ignore_types
+            .iter()
+            .any(|pattern| mime_matches(pattern, mime))
+        {
+            return Some("ignored type");
+        }
+
+        if self.config.min_size.is_some_and(|min| bytes.len() < min) {
+            return Some("below minimum size");
+        }
+
+        if self.config.max_size.is_some_and(|max| bytes.len() > max) {
+            return Some("above maximum size");
+        }
+
+        if !self.patterns.is_empty() {
+            if let Some(text) = snapshot.text() {
+                if self.patterns.iter().any(|pattern| pattern.is_match(&text)) {
+                    return Some("matches an ignore pattern");
+                }
+            }
+        }
+
+        None
+    }
+}
+
+pub(crate) fn mime_matches(pattern: &str, mime: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(prefix) => mime
+            .split_once('/')
+            .is_some_and(|(kind, _)| kind.eq_ignore_ascii_case(prefix)),
+        None => pattern.eq_ignore_ascii_case(mime),
+    }
+}
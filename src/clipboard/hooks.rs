@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tracing::{debug, error, warn};
+
+use super::watcher::ClipboardEvent;
+use crate::{ClaudeUtilsError, Result};
+
+pub const DEFAULT_HOOK_TIMEOUT_MS: u64 = 10_000;
+pub const DEFAULT_MAX_CONCURRENT_HOOKS: usize = 4;
+
+/// One `[[watch.hooks]]` entry of the config file.
+///
+/// ```toml
+/// [[watch.hooks]]
+/// name = "upload"
+/// command = ["/usr/local/bin/upload-to-bucket", "--public"]
+/// rules = ["images"]
+/// timeout_ms = 30000
+/// replace_clipboard = true
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookConfig {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Program and arguments; the staged path is appended when there is one
+    pub command: Vec<String>,
+    /// Names of the rules whose copies the hook runs for; empty means all
+    #[serde(default)]
+    pub rules: Vec<String>,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Put the command's output on the clipboard in place of the path, when
+    /// the rule rewrites the clipboard. The processor waits for such hooks;
+    /// the others run in the background.
+    #[serde(default)]
+    pub replace_clipboard: bool,
+}
+
+fn default_timeout_ms() -> u64 {
+    DEFAULT_HOOK_TIMEOUT_MS
+}
+
+impl HookConfig {
+    fn name(&self) -> &str {
+        self.name
+            .as_deref()
+            .or_else(|| self.command.first().map(String::as_str))
+            .unwrap_or("hook")
+    }
+
+    fn applies_to(&self, rule: &str) -> bool {
+        self.rules.is_empty() || self.rules.iter().any(|name| name == rule)
+    }
+}
+
+/// Runs the configured hooks for each processed copy, at most
+/// `max_concurrent` background commands at a time.
+#[derive(Debug, Clone)]
+pub struct HookRunner {
+    hooks: Arc<Vec<HookConfig>>,
+    permits: Arc<Semaphore>,
+}
+
+impl HookRunner {
+    pub fn new(hooks: Vec<HookConfig>, max_concurrent: usize) -> Self {
+        Self {
+            hooks: Arc::new(hooks),
+            permits: Arc::new(Semaphore::new(max_concurrent.max(1))),
+        }
+    }
+
+    /// Runs the hooks that apply to copies handled by `rule`. Returns the
+    /// output of the last `replace_clipboard` hook that succeeded with any.
+    pub async fn run(&self, rule: &str, event: &ClipboardEvent) -> Option<String> {
+        let hooks: Vec<_> = self
+            .hooks
+            .iter()
+            .filter(|hook| hook.applies_to(rule))
+            .cloned()
+            .collect();
+        if hooks.is_empty() {
+            return None;
+        }
+
+        let input = Arc::new(HookInput::new(rule, event));
+        let mut replacement = None;
+
+        for hook in hooks {
+            // Waited for one at a time, so these take no permit and never
+            // queue behind background hooks
+            if hook.replace_clipboard {
+                match run_hook(None, &hook, &input).await {
+                    Ok(Some(output)) => replacement = Some(output),
+                    Ok(None) => {}
+                    Err(e) => error!("Hook {} failed: {}", hook.name(), e),
+                }
+            } else {
+                let permits = self.permits.clone();
+                let input = input.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = run_hook(Some(&permits), &hook, &input).await {
+                        error!("Hook {} failed: {}", hook.name(), e);
+                    }
+                });
+            }
+        }
+
+        replacement
+    }
+}
+
+/// What a hook is told about the copy: the event as JSON on stdin, and the
+/// main fields again as `CLAUDE_UTILS_*` environment variables.
+struct HookInput {
+    json: Vec<u8>,
+    env: Vec<(&'static str, String)>,
+    staged_path: Option<String>,
+}
+
+impl HookInput {
+    fn new(rule: &str, event: &ClipboardEvent) -> Self {
+        let (mime_type, size) = event
+            .snapshot
+            .formats()
+            .first()
+            .map(|(mime, bytes)| (mime.clone(), bytes.len()))
+            .unwrap_or_default();
+        let staged_path = event
+            .staged_path
+            .as_ref()
+            .map(|path| path.to_string_lossy().to_string());
+        let symlink_path = event
+            .symlink_path
+            .as_ref()
+            .map(|path| path.to_string_lossy().to_string());
+        let selection = event.snapshot.selection();
+
+        let json = json!({
+            "sequence": event.sequence,
+            "rule": rule,
+            "selection": selection,
+            "mime_type": mime_type,
+            "mime_types": event.mime_types,
+            "size": size,
+            "staged_path": staged_path,
+            "symlink_path": symlink_path,
+            "content": event.content,
+        });
+
+        let mut env = vec![
+            ("CLAUDE_UTILS_SEQUENCE", event.sequence.to_string()),
+            ("CLAUDE_UTILS_RULE", rule.to_string()),
+            ("CLAUDE_UTILS_SELECTION", selection.to_string()),
+            ("CLAUDE_UTILS_MIME_TYPE", mime_type),
+            ("CLAUDE_UTILS_SIZE", size.to_string()),
+        ];
+        if let Some(path) = &staged_path {
+            env.push(("CLAUDE_UTILS_STAGED_PATH", path.clone()));
+        }
+        if let Some(path) = symlink_path {
+            env.push(("CLAUDE_UTILS_SYMLINK_PATH", path));
+        }
+
+        Self {
+            json: serde_json::to_vec(&json).unwrap_or_default(),
+            env,
+            staged_path,
+        }
+    }
+}
+
+/// Runs one hook to completion, killing it once its timeout passes, after
+/// taking one of `permits` if given. Returns its trimmed stdout when it
+/// replaces the clipboard and printed anything.
+async fn run_hook(
+    permits: Option<&Semaphore>,
+    hook: &HookConfig,
+    input: &HookInput,
+) -> Result<Option<String>> {
+    let (program, args) = hook
+        .command
+        .split_first()
+        .ok_or_else(|| ClaudeUtilsError::Config(format!("Hook {} has no command", hook.name())))?;
+
+    let _permit = match permits {
+        Some(permits) => Some(
+            permits
+                .acquire()
+                .await
+                .map_err(|e| ClaudeUtilsError::Server(e.to_string()))?,
+        ),
+        None => None,
+    };
+    debug!("Running hook {}", hook.name());
+
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .args(&input.staged_path)
+        .envs(input.env.iter().map(|(key, value)| (key, value)))
+        .stdin(Stdio::piped())
+        .stdout(if hook.replace_clipboard {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let mut child = command.spawn()?;
+    let stdin = child.stdin.take();
+    let write_input = async {
+        if let Some(mut stdin) = stdin {
+            // A hook that ignores its input may exit before reading it
+            if let Err(e) = stdin.write_all(&input.json).await {
+                debug!("Hook {} did not read its input: {}", hook.name(), e);
+            }
+        }
+    };
+    // Output is drained while the input is written, so a hook that prints
+    // before reading cannot fill its pipe and stall
+    let run = async {
+        let ((), output) = tokio::join!(write_input, child.wait_with_output());
+        output
+    };
+
+    let output = tokio::time::timeout(Duration::from_millis(hook.timeout_ms), run)
+        .await
+        .map_err(|_| {
+            ClaudeUtilsError::Server(format!("timed out after {}ms", hook.timeout_ms))
+        })??;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(ClaudeUtilsError::Server(format!(
+            "exited with {}: {}",
+            output.status,
+            stderr.trim()
+        )));
+    }
+    if !output.stderr.is_empty() {
+        warn!(
+            "Hook {}: {}",
+            hook.name(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok(Some(stdout).filter(|stdout| !stdout.is_empty()))
+}
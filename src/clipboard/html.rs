@@ -0,0 +1,22 @@
+use super::ClipboardContent;
+
+/// Renders clipboard HTML as Markdown, keeping links, lists, code blocks
+/// and tables so the structure survives the trip into a prompt.
+pub fn html_to_markdown(html: &str) -> String {
+    html2md::parse_html(html).trim().to_string()
+}
+
+impl ClipboardContent {
+    /// Converts HTML content to Markdown; everything else is returned as is.
+    pub fn into_markdown(self) -> ClipboardContent {
+        match self {
+            ClipboardContent::Html {
+                data, truncated, ..
+            } => ClipboardContent::Markdown {
+                data: html_to_markdown(&data),
+                truncated,
+            },
+            other => other,
+        }
+    }
+}
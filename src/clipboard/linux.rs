@@ -0,0 +1,830 @@
+//! Direct X11 and Wayland access for what arboard does not expose: the list
+//! of targets a selection owner offers, targets arboard cannot convert, and
+//! owning a selection with several targets at once, and hearing about new
+//! owners without polling (XFixes on X11, data-control on Wayland).
+
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use super::backend::{OwnerChange, Selection};
+use crate::{ClaudeUtilsError, Result};
+
+/// How long to wait for the selection owner to answer.
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// X11 targets that describe the selection rather than hold its contents.
+const META_TARGETS: &[&str] = &["TARGETS", "MULTIPLE", "TIMESTAMP", "SAVE_TARGETS", "DELETE"];
+
+/// Every target offered for `selection`, in the owner's order.
+pub(crate) fn targets(selection: Selection) -> Result<Vec<String>> {
+    if use_wayland() {
+        match wayland::targets(selection) {
+            Ok(targets) => return Ok(targets),
+            // XWayland may still answer when the compositor lacks data-control
+            Err(e) if has_x11() => tracing::debug!("Wayland targets unavailable: {}", e),
+            Err(e) => return Err(e),
+        }
+    }
+
+    let mut targets = x11::targets(selection)?;
+    targets.retain(|target| !META_TARGETS.contains(&target.as_str()));
+    Ok(targets)
+}
+
+/// The exact bytes of one target, without conversion.
+pub(crate) fn read(selection: Selection, target: &str) -> Result<Vec<u8>> {
+    if use_wayland() {
+        match wayland::read(selection, target) {
+            Ok(bytes) => return Ok(bytes),
+            Err(e) if has_x11() => tracing::debug!("Wayland read failed: {}", e),
+            Err(e) => return Err(e),
+        }
+    }
+
+    x11::read(selection, target)
+}
+
+/// How many bytes the owner offers as `target`. X11 tells without sending
+/// the data; on Wayland the offer has to be read through.
+pub(crate) fn size(selection: Selection, target: &str) -> Result<usize> {
+    if use_wayland() {
+        match wayland::size(selection, target) {
+            Ok(size) => return Ok(size),
+            Err(e) if has_x11() => tracing::debug!("Wayland size unavailable: {}", e),
+            Err(e) => return Err(e),
+        }
+    }
+
+    x11::size(selection, target)
+}
+
+/// Takes ownership of `selection` and serves every one of `formats` from it
+/// until another application copies something. With `wait` this blocks
+/// until then; otherwise a background thread keeps serving.
+pub(crate) fn offer(selection: Selection, formats: &[(String, Vec<u8>)], wait: bool) -> Result<()> {
+    if use_wayland() {
+        match wayland::offer(selection, formats, wait) {
+            Ok(()) => return Ok(()),
+            Err(e) if has_x11() => tracing::debug!("Wayland copy failed: {}", e),
+            Err(e) => return Err(e),
+        }
+    }
+
+    x11::offer(selection, formats, wait)
+}
+
+/// Sends a change on `changes` each time one of `selections` gets a new
+/// owner. Fails where there is no such notification, leaving the caller to
+/// poll.
+pub(crate) fn watch_owners(
+    selections: &[Selection],
+    changes: mpsc::UnboundedSender<OwnerChange>,
+) -> Result<()> {
+    if use_wayland() {
+        // No XFixes fallback: XWayland only sees X clients copy
+        return wayland::watch_owners(selections, changes);
+    }
+
+    x11::watch_owners(selections, changes)
+}
+
+fn use_wayland() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some_and(|v| !v.is_empty())
+}
+
+fn has_x11() -> bool {
+    std::env::var_os("DISPLAY").is_some_and(|v| !v.is_empty())
+}
+
+fn clipboard_error(e: impl std::fmt::Display) -> ClaudeUtilsError {
+    ClaudeUtilsError::Clipboard(e.to_string())
+}
+
+mod x11 {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use x11_clipboard::{Atom, Clipboard};
+    use x11rb::connection::{Connection, RequestConnection};
+    use x11rb::protocol::xfixes::{ConnectionExt as _, SelectionEventMask};
+    use x11rb::protocol::xproto::{
+        AtomEnum, ChangeWindowAttributesAux, ConnectionExt, CreateWindowAux, EventMask, PropMode,
+        Property, SelectionNotifyEvent, SelectionRequestEvent, Window, WindowClass,
+        SELECTION_NOTIFY_EVENT,
+    };
+    use x11rb::protocol::Event;
+    use x11rb::rust_connection::RustConnection;
+    use x11rb::wrapper::ConnectionExt as _;
+    use x11rb::{COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE};
+
+    use std::time::{Duration, Instant};
+
+    use super::{clipboard_error, mpsc, OwnerChange, Selection, TRANSFER_TIMEOUT};
+    use crate::clipboard::backend::is_text_mime;
+    use crate::{ClaudeUtilsError, Result};
+
+    /// Names we answer to for the plain-text entry, legacy ones included.
+    const TEXT_TARGETS: &[&str] = &[
+        "UTF8_STRING",
+        "text/plain;charset=utf-8",
+        "text/plain",
+        "STRING",
+        "TEXT",
+    ];
+
+    fn selection_atom(clipboard: &Clipboard, selection: Selection) -> Result<Atom> {
+        let atoms = &clipboard.getter.atoms;
+
+        match selection {
+            Selection::Clipboard => Ok(atoms.clipboard),
+            Selection::Primary => Ok(atoms.primary),
+            Selection::Secondary => clipboard
+                .getter
+                .get_atom("SECONDARY")
+                .map_err(clipboard_error),
+        }
+    }
+
+    pub(super) fn targets(selection: Selection) -> Result<Vec<String>> {
+        let clipboard = Clipboard::new().map_err(clipboard_error)?;
+        let atoms = &clipboard.getter.atoms;
+
+        let raw = clipboard
+            .load(
+                selection_atom(&clipboard, selection)?,
+                atoms.targets,
+                atoms.property,
+                TRANSFER_TIMEOUT,
+            )
+            .map_err(clipboard_error)?;
+
+        // TARGETS is a list of 32-bit atoms in our byte order
+        raw.chunks_exact(4)
+            .map(|chunk| u32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .map(|atom| {
+                let reply = clipboard
+                    .getter
+                    .connection
+                    .get_atom_name(atom)
+                    .map_err(clipboard_error)?
+                    .reply()
+                    .map_err(clipboard_error)?;
+
+                Ok(String::from_utf8_lossy(&reply.name).into_owned())
+            })
+            .collect()
+    }
+
+    pub(super) fn read(selection: Selection, target: &str) -> Result<Vec<u8>> {
+        let clipboard = Clipboard::new().map_err(clipboard_error)?;
+        let target = clipboard.getter.get_atom(target).map_err(clipboard_error)?;
+
+        clipboard
+            .load(
+                selection_atom(&clipboard, selection)?,
+                target,
+                clipboard.getter.atoms.property,
+                TRANSFER_TIMEOUT,
+            )
+            .map_err(clipboard_error)
+    }
+
+    /// Has the owner convert `target` and looks at the property's length
+    /// without reading it. For an INCR transfer that is the lower bound the
+    /// owner announces, and the transfer is never started.
+    pub(super) fn size(selection: Selection, target: &str) -> Result<usize> {
+        let clipboard = Clipboard::new().map_err(clipboard_error)?;
+        let getter = &clipboard.getter;
+        let conn = &getter.connection;
+        let target = getter.get_atom(target).map_err(clipboard_error)?;
+        let property = getter.atoms.property;
+
+        conn.convert_selection(
+            getter.window,
+            selection_atom(&clipboard, selection)?,
+            target,
+            property,
+            CURRENT_TIME,
+        )
+        .map_err(clipboard_error)?
+        .check()
+        .map_err(clipboard_error)?;
+
+        let deadline = Instant::now() + TRANSFER_TIMEOUT;
+        loop {
+            match conn.poll_for_event().map_err(clipboard_error)? {
+                Some(Event::SelectionNotify(event)) if event.requestor == getter.window => {
+                    if event.property == NONE {
+                        return Err(ClaudeUtilsError::Clipboard(
+                            "The selection owner refused the conversion".to_string(),
+                        ));
+                    }
+                    break;
+                }
+                Some(_) => {}
+                None if Instant::now() >= deadline => {
+                    return Err(ClaudeUtilsError::Clipboard(
+                        "Timed out waiting for the selection owner".to_string(),
+                    ))
+                }
+                None => std::thread::sleep(Duration::from_millis(5)),
+            }
+        }
+
+        let reply = conn
+            .get_property(false, getter.window, property, AtomEnum::ANY, 0, 1)
+            .map_err(clipboard_error)?
+            .reply()
+            .map_err(clipboard_error)?;
+
+        if reply.type_ == getter.atoms.incr {
+            return Ok(reply
+                .value32()
+                .and_then(|mut value| value.next())
+                .unwrap_or(0) as usize);
+        }
+
+        conn.delete_property(getter.window, property)
+            .map_err(clipboard_error)?;
+        Ok(reply.value.len() + reply.bytes_after as usize)
+    }
+
+    fn selection_name(selection: Selection) -> &'static str {
+        match selection {
+            Selection::Clipboard => "CLIPBOARD",
+            Selection::Primary => "PRIMARY",
+            Selection::Secondary => "SECONDARY",
+        }
+    }
+
+    fn intern(conn: &RustConnection, name: &str) -> Result<Atom> {
+        Ok(conn
+            .intern_atom(false, name.as_bytes())
+            .map_err(clipboard_error)?
+            .reply()
+            .map_err(clipboard_error)?
+            .atom)
+    }
+
+    /// An INCR transfer in progress, fed one chunk per property deletion.
+    struct Transfer {
+        target: Atom,
+        data: Arc<Vec<u8>>,
+        offset: usize,
+    }
+
+    /// A selection owner with its own connection and window.
+    struct Owner {
+        conn: RustConnection,
+        window: Window,
+        selection: Atom,
+        targets_atom: Atom,
+        incr: Atom,
+        offered: HashMap<Atom, Arc<Vec<u8>>>,
+        chunk_size: usize,
+        transfers: HashMap<(Window, Atom), Transfer>,
+    }
+
+    pub(super) fn offer(
+        selection: Selection,
+        formats: &[(String, Vec<u8>)],
+        wait: bool,
+    ) -> Result<()> {
+        let (conn, screen_num) = x11rb::connect(None).map_err(clipboard_error)?;
+        let root = conn.setup().roots[screen_num].root;
+
+        let window = conn.generate_id().map_err(clipboard_error)?;
+        conn.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            window,
+            root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            COPY_FROM_PARENT,
+            &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )
+        .map_err(clipboard_error)?;
+
+        let selection_atom = intern(&conn, selection_name(selection))?;
+
+        let mut offered = HashMap::new();
+        for (mime, data) in formats {
+            let data = Arc::new(data.clone());
+
+            if is_text_mime(mime) {
+                for name in TEXT_TARGETS {
+                    offered.insert(intern(&conn, name)?, data.clone());
+                }
+            } else {
+                offered.insert(intern(&conn, mime)?, data);
+            }
+        }
+
+        conn.set_selection_owner(window, selection_atom, CURRENT_TIME)
+            .map_err(clipboard_error)?;
+
+        let current_owner = conn
+            .get_selection_owner(selection_atom)
+            .map_err(clipboard_error)?
+            .reply()
+            .map_err(clipboard_error)?
+            .owner;
+        if current_owner != window {
+            return Err(ClaudeUtilsError::Clipboard(
+                "Could not take ownership of the selection".to_string(),
+            ));
+        }
+
+        let owner = Owner {
+            targets_atom: intern(&conn, "TARGETS")?,
+            incr: intern(&conn, "INCR")?,
+            chunk_size: (conn.maximum_request_bytes() / 4).max(4096),
+            conn,
+            window,
+            selection: selection_atom,
+            offered,
+            transfers: HashMap::new(),
+        };
+
+        let handle = std::thread::spawn(move || {
+            if let Err(e) = owner.serve() {
+                tracing::warn!("Selection owner stopped: {}", e);
+            }
+        });
+
+        if wait {
+            let _ = handle.join();
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn watch_owners(
+        selections: &[Selection],
+        changes: mpsc::UnboundedSender<OwnerChange>,
+    ) -> Result<()> {
+        let (conn, screen_num) = x11rb::connect(None).map_err(clipboard_error)?;
+        let root = conn.setup().roots[screen_num].root;
+
+        // Fails with UnsupportedExtension when the server has no XFixes
+        conn.xfixes_query_version(5, 0)
+            .map_err(clipboard_error)?
+            .reply()
+            .map_err(clipboard_error)?;
+
+        let mask = SelectionEventMask::SET_SELECTION_OWNER
+            | SelectionEventMask::SELECTION_WINDOW_DESTROY
+            | SelectionEventMask::SELECTION_CLIENT_CLOSE;
+
+        let mut watched = HashMap::new();
+        for &selection in selections {
+            let atom = intern(&conn, selection_name(selection))?;
+            conn.xfixes_select_selection_input(root, atom, mask)
+                .map_err(clipboard_error)?
+                .check()
+                .map_err(clipboard_error)?;
+            watched.insert(atom, selection);
+        }
+
+        std::thread::spawn(move || loop {
+            match conn.wait_for_event() {
+                Ok(Event::XfixesSelectionNotify(event)) => {
+                    let Some(&selection) = watched.get(&event.selection) else {
+                        continue;
+                    };
+                    // XFixes does not say what the new owner offers
+                    if changes.send(selection.into()).is_err() {
+                        // The watcher has gone away
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!("XFixes selection watch stopped: {}", e);
+                    break;
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    impl Owner {
+        /// Answers requests until another client takes the selection.
+        fn serve(mut self) -> Result<()> {
+            loop {
+                match self.conn.wait_for_event().map_err(clipboard_error)? {
+                    Event::SelectionRequest(request) => self.answer(request)?,
+                    Event::PropertyNotify(event) if event.state == Property::DELETE => {
+                        self.continue_transfer(event.window, event.atom)?
+                    }
+                    Event::SelectionClear(event) if event.selection == self.selection => {
+                        return self
+                            .conn
+                            .destroy_window(self.window)
+                            .map(|_| ())
+                            .map_err(clipboard_error);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        fn answer(&mut self, request: SelectionRequestEvent) -> Result<()> {
+            // Obsolete clients leave the property unset
+            let property = if request.property == NONE {
+                request.target
+            } else {
+                request.property
+            };
+
+            let stored = if request.target == self.targets_atom {
+                let mut targets: Vec<Atom> = self.offered.keys().copied().collect();
+                targets.push(self.targets_atom);
+
+                self.conn
+                    .change_property32(
+                        PropMode::REPLACE,
+                        request.requestor,
+                        property,
+                        AtomEnum::ATOM,
+                        &targets,
+                    )
+                    .map_err(clipboard_error)?;
+                true
+            } else if let Some(data) = self.offered.get(&request.target).cloned() {
+                if data.len() > self.chunk_size {
+                    self.start_transfer(request.requestor, property, request.target, data)?;
+                } else {
+                    self.conn
+                        .change_property8(
+                            PropMode::REPLACE,
+                            request.requestor,
+                            property,
+                            request.target,
+                            &data,
+                        )
+                        .map_err(clipboard_error)?;
+                }
+                true
+            } else {
+                false
+            };
+
+            let notify = SelectionNotifyEvent {
+                response_type: SELECTION_NOTIFY_EVENT,
+                sequence: 0,
+                time: request.time,
+                requestor: request.requestor,
+                selection: request.selection,
+                target: request.target,
+                property: if stored { property } else { NONE },
+            };
+
+            self.conn
+                .send_event(false, request.requestor, EventMask::NO_EVENT, notify)
+                .map_err(clipboard_error)?;
+            self.conn.flush().map_err(clipboard_error)
+        }
+
+        fn start_transfer(
+            &mut self,
+            requestor: Window,
+            property: Atom,
+            target: Atom,
+            data: Arc<Vec<u8>>,
+        ) -> Result<()> {
+            // Chunks go out as the requestor deletes the property
+            self.conn
+                .change_window_attributes(
+                    requestor,
+                    &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+                )
+                .map_err(clipboard_error)?;
+
+            self.conn
+                .change_property32(
+                    PropMode::REPLACE,
+                    requestor,
+                    property,
+                    self.incr,
+                    &[data.len() as u32],
+                )
+                .map_err(clipboard_error)?;
+
+            self.transfers.insert(
+                (requestor, property),
+                Transfer {
+                    target,
+                    data,
+                    offset: 0,
+                },
+            );
+            Ok(())
+        }
+
+        fn continue_transfer(&mut self, requestor: Window, property: Atom) -> Result<()> {
+            let Some(transfer) = self.transfers.get_mut(&(requestor, property)) else {
+                return Ok(());
+            };
+
+            let end = (transfer.offset + self.chunk_size).min(transfer.data.len());
+            let chunk = &transfer.data[transfer.offset..end];
+
+            // The empty chunk after the last one ends the transfer
+            self.conn
+                .change_property8(
+                    PropMode::REPLACE,
+                    requestor,
+                    property,
+                    transfer.target,
+                    chunk,
+                )
+                .map_err(clipboard_error)?;
+
+            if chunk.is_empty() {
+                self.transfers.remove(&(requestor, property));
+            } else {
+                transfer.offset = end;
+            }
+
+            self.conn.flush().map_err(clipboard_error)
+        }
+    }
+}
+
+/// Everything here goes through `ext-data-control` or `wlr-data-control`,
+/// which work without a focused window but are missing on GNOME.
+pub(super) mod wayland {
+    use std::collections::HashMap;
+    use std::io::Read;
+    use wayland_client::backend::ObjectId;
+    use wayland_client::globals::{registry_queue_init, GlobalListContents};
+    use wayland_client::protocol::wl_registry::{self, WlRegistry};
+    use wayland_client::protocol::wl_seat::WlSeat;
+    use wayland_client::{
+        delegate_noop, event_created_child, Connection, Dispatch, Proxy, QueueHandle,
+    };
+    use wayland_protocols::ext::data_control::v1::client::{
+        ext_data_control_device_v1::{self, ExtDataControlDeviceV1},
+        ext_data_control_manager_v1::ExtDataControlManagerV1,
+        ext_data_control_offer_v1::{self, ExtDataControlOfferV1},
+    };
+    use wayland_protocols_wlr::data_control::v1::client::{
+        zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+        zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+        zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+    };
+    use wl_clipboard_rs::copy::{self, MimeSource, Options, Source};
+    use wl_clipboard_rs::paste::{
+        get_contents, get_mime_types_ordered, ClipboardType, MimeType, Seat,
+    };
+
+    use super::{clipboard_error, mpsc, OwnerChange, Selection};
+    use crate::clipboard::backend::is_text_mime;
+    use crate::{ClaudeUtilsError, Result};
+
+    fn clipboard_type(selection: Selection) -> Result<ClipboardType> {
+        match selection {
+            Selection::Clipboard => Ok(ClipboardType::Regular),
+            Selection::Primary => Ok(ClipboardType::Primary),
+            Selection::Secondary => Err(ClaudeUtilsError::Clipboard(
+                "Wayland has no secondary selection".to_string(),
+            )),
+        }
+    }
+
+    pub(crate) fn targets(selection: Selection) -> Result<Vec<String>> {
+        get_mime_types_ordered(clipboard_type(selection)?, Seat::Unspecified)
+            .map_err(clipboard_error)
+    }
+
+    pub(crate) fn read(selection: Selection, mime: &str) -> Result<Vec<u8>> {
+        let (mut pipe, _) = get_contents(
+            clipboard_type(selection)?,
+            Seat::Unspecified,
+            MimeType::Specific(mime),
+        )
+        .map_err(clipboard_error)?;
+
+        let mut bytes = Vec::new();
+        pipe.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Data-control offers carry no size, so this reads the offer through
+    /// without keeping it.
+    pub(crate) fn size(selection: Selection, mime: &str) -> Result<usize> {
+        let (mut pipe, _) = get_contents(
+            clipboard_type(selection)?,
+            Seat::Unspecified,
+            MimeType::Specific(mime),
+        )
+        .map_err(clipboard_error)?;
+
+        Ok(std::io::copy(&mut pipe, &mut std::io::sink())? as usize)
+    }
+
+    fn copy_type(selection: Selection) -> Result<copy::ClipboardType> {
+        match clipboard_type(selection)? {
+            ClipboardType::Primary => Ok(copy::ClipboardType::Primary),
+            ClipboardType::Regular => Ok(copy::ClipboardType::Regular),
+        }
+    }
+
+    pub(crate) fn offer(
+        selection: Selection,
+        formats: &[(String, Vec<u8>)],
+        wait: bool,
+    ) -> Result<()> {
+        let clipboard = copy_type(selection)?;
+
+        let sources = formats
+            .iter()
+            .map(|(mime, data)| MimeSource {
+                source: Source::Bytes(data.clone().into_boxed_slice()),
+                mime_type: if is_text_mime(mime) {
+                    copy::MimeType::Text
+                } else {
+                    copy::MimeType::Specific(mime.clone())
+                },
+            })
+            .collect();
+
+        let mut options = Options::new();
+        options.clipboard(clipboard).foreground(wait);
+        options.copy_multi(sources).map_err(clipboard_error)
+    }
+
+    pub(crate) fn clear(selection: Selection) -> Result<()> {
+        copy::clear(copy_type(selection)?, copy::Seat::All).map_err(clipboard_error)
+    }
+
+    /// State of the thread listening on a data-control device.
+    struct Watch {
+        selections: Vec<Selection>,
+        changes: mpsc::UnboundedSender<OwnerChange>,
+        /// MIME types of each offer, announced before it is selected
+        offers: HashMap<ObjectId, Vec<String>>,
+        stopped: bool,
+    }
+
+    impl Watch {
+        fn selected(&mut self, selection: Selection, offer: Option<ObjectId>) {
+            let mime_types = offer.and_then(|offer| self.offers.remove(&offer));
+            if !self.selections.contains(&selection) {
+                return;
+            }
+
+            let change = OwnerChange {
+                selection,
+                // No offer means the selection was cleared
+                mime_types: Some(mime_types.unwrap_or_default()),
+            };
+            if self.changes.send(change).is_err() {
+                // The watcher has gone away
+                self.stopped = true;
+            }
+        }
+    }
+
+    pub(crate) fn watch_owners(
+        selections: &[Selection],
+        changes: mpsc::UnboundedSender<OwnerChange>,
+    ) -> Result<()> {
+        let conn = Connection::connect_to_env().map_err(clipboard_error)?;
+        let (globals, mut queue) = registry_queue_init::<Watch>(&conn).map_err(clipboard_error)?;
+        let qh = queue.handle();
+
+        let seat: WlSeat = globals.bind(&qh, 2..=9, ()).map_err(clipboard_error)?;
+
+        // KDE and older wlroots only have the wlr protocol, whose PRIMARY
+        // support arrived in version 2
+        if let Ok(manager) = globals.bind::<ExtDataControlManagerV1, _, _>(&qh, 1..=1, ()) {
+            manager.get_data_device(&seat, &qh, ());
+        } else {
+            let manager: ZwlrDataControlManagerV1 = globals.bind(&qh, 1..=2, ()).map_err(|_| {
+                ClaudeUtilsError::Clipboard(
+                    "Compositor supports neither ext-data-control nor wlr-data-control".to_string(),
+                )
+            })?;
+            manager.get_data_device(&seat, &qh, ());
+        }
+
+        let mut state = Watch {
+            selections: selections.to_vec(),
+            changes,
+            offers: HashMap::new(),
+            stopped: false,
+        };
+
+        // Surface a refused device here rather than on the thread
+        queue.roundtrip(&mut state).map_err(clipboard_error)?;
+
+        std::thread::spawn(move || {
+            let _conn = conn;
+
+            while !state.stopped {
+                if let Err(e) = queue.blocking_dispatch(&mut state) {
+                    tracing::warn!("Wayland selection watch stopped: {}", e);
+                    break;
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    impl Dispatch<WlRegistry, GlobalListContents> for Watch {
+        fn event(
+            _: &mut Self,
+            _: &WlRegistry,
+            _: wl_registry::Event,
+            _: &GlobalListContents,
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    delegate_noop!(Watch: ignore WlSeat);
+    delegate_noop!(Watch: ExtDataControlManagerV1);
+    delegate_noop!(Watch: ZwlrDataControlManagerV1);
+
+    /// Records the MIME types an offer announces; its contents are read
+    /// separately.
+    macro_rules! dispatch_offer {
+        ($module:ident, $offer:ident) => {
+            impl Dispatch<$offer, ()> for Watch {
+                fn event(
+                    state: &mut Self,
+                    offer: &$offer,
+                    event: $module::Event,
+                    _: &(),
+                    _: &Connection,
+                    _: &QueueHandle<Self>,
+                ) {
+                    if let $module::Event::Offer { mime_type } = event {
+                        state.offers.entry(offer.id()).or_default().push(mime_type);
+                    }
+                }
+            }
+        };
+    }
+
+    dispatch_offer!(ext_data_control_offer_v1, ExtDataControlOfferV1);
+    dispatch_offer!(zwlr_data_control_offer_v1, ZwlrDataControlOfferV1);
+
+    /// Both protocols have the same device events under different names.
+    macro_rules! dispatch_device {
+        ($module:ident, $device:ident, $offer:ident) => {
+            impl Dispatch<$device, ()> for Watch {
+                fn event(
+                    state: &mut Self,
+                    _: &$device,
+                    event: $module::Event,
+                    _: &(),
+                    _: &Connection,
+                    _: &QueueHandle<Self>,
+                ) {
+                    match event {
+                        $module::Event::Selection { id } => {
+                            let offer = id.map(|offer| {
+                                offer.destroy();
+                                offer.id()
+                            });
+                            state.selected(Selection::Clipboard, offer);
+                        }
+                        $module::Event::PrimarySelection { id } => {
+                            let offer = id.map(|offer| {
+                                offer.destroy();
+                                offer.id()
+                            });
+                            state.selected(Selection::Primary, offer);
+                        }
+                        $module::Event::Finished => state.stopped = true,
+                        _ => {}
+                    }
+                }
+
+                event_created_child!(Watch, $device, [
+                    $module::EVT_DATA_OFFER_OPCODE => ($offer, ()),
+                ]);
+            }
+        };
+    }
+
+    dispatch_device!(
+        ext_data_control_device_v1,
+        ExtDataControlDeviceV1,
+        ExtDataControlOfferV1
+    );
+    dispatch_device!(
+        zwlr_data_control_device_v1,
+        ZwlrDataControlDeviceV1,
+        ZwlrDataControlOfferV1
+    );
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::backend::{
+    decode_image, encode_png, is_text_mime, unsupported_format, ClipboardBackend, RawImage,
+    Selection, MIME_PNG, MIME_TEXT,
+};
+use crate::{ClaudeUtilsError, Result};
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    formats: Vec<(String, Vec<u8>)>,
+    image: Option<RawImage>,
+}
+
+/// A process-local clipboard with no display dependency.
+///
+/// Clones share the same contents, so a test can keep one handle to play
+/// the part of another application while a `ClipboardManager` owns another.
+/// Every selection is kept separately, as on X11.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryClipboard {
+    state: Arc<Mutex<HashMap<Selection, MemoryState>>>,
+}
+
+impl MemoryClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, HashMap<Selection, MemoryState>>> {
+        self.state
+            .lock()
+            .map_err(|e| ClaudeUtilsError::Clipboard(format!("Lock error: {e}")))
+    }
+}
+
+impl ClipboardBackend for MemoryClipboard {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn supports_selection(&self, _selection: Selection) -> bool {
+        true
+    }
+
+    fn get_text(&mut self, selection: Selection) -> Result<String> {
+        let bytes = self.get_format(selection, MIME_TEXT)?;
+
+        String::from_utf8(bytes).map_err(|e| ClaudeUtilsError::Clipboard(e.to_string()))
+    }
+
+    fn set_text(&mut self, selection: Selection, text: &str) -> Result<()> {
+        self.set_formats(
+            selection,
+            &[(MIME_TEXT.to_string(), text.as_bytes().to_vec())],
+        )
+    }
+
+    fn get_image(&mut self, selection: Selection) -> Result<RawImage> {
+        let selections = self.lock()?;
+        let state = selections.get(&selection);
+
+        if let Some(image) = state.and_then(|state| state.image.as_ref()) {
+            return Ok(image.clone());
+        }
+
+        match state.and_then(|state| {
+            state
+                .formats
+                .iter()
+                .find(|(mime, _)| mime.starts_with("image/"))
+        }) {
+            Some((_, bytes)) => decode_image(bytes),
+            None => Err(ClaudeUtilsError::Clipboard(
+                "No image in clipboard".to_string(),
+            )),
+        }
+    }
+
+    fn set_image(&mut self, selection: Selection, image: &RawImage) -> Result<()> {
+        self.lock()?.insert(
+            selection,
+            MemoryState {
+                formats: Vec::new(),
+                image: Some(image.clone()),
+            },
+        );
+        Ok(())
+    }
+
+    fn formats(&mut self, selection: Selection) -> Result<Vec<String>> {
+        let selections = self.lock()?;
+
+        Ok(match selections.get(&selection) {
+            Some(MemoryState { image: Some(_), .. }) => vec![MIME_PNG.to_string()],
+            Some(state) => state.formats.iter().map(|(mime, _)| mime.clone()).collect(),
+            None => Vec::new(),
+        })
+    }
+
+    fn get_format(&mut self, selection: Selection, mime: &str) -> Result<Vec<u8>> {
+        let selections = self.lock()?;
+        let Some(state) = selections.get(&selection) else {
+            return Err(unsupported_format(self.name(), mime));
+        };
+
+        let found = state
+            .formats
+            .iter()
+            .find(|(offered, _)| offered == mime || (is_text_mime(offered) && is_text_mime(mime)));
+
+        if let Some((_, bytes)) = found {
+            return Ok(bytes.clone());
+        }
+
+        match &state.image {
+            Some(image) if mime == MIME_PNG => encode_png(image),
+            _ => Err(unsupported_format(self.name(), mime)),
+        }
+    }
+
+    fn format_size(&mut self, selection: Selection, mime: &str) -> Option<usize> {
+        self.get_format(selection, mime)
+            .ok()
+            .map(|bytes| bytes.len())
+    }
+
+    fn set_formats(&mut self, selection: Selection, formats: &[(String, Vec<u8>)]) -> Result<()> {
+        self.lock()?.insert(
+            selection,
+            MemoryState {
+                formats: formats.to_vec(),
+                image: None,
+            },
+        );
+        Ok(())
+    }
+}
@@ -1,10 +1,35 @@
+pub mod backend;
+pub mod delivery;
+pub mod events;
+pub mod file;
+pub mod files;
+pub mod filter;
+pub mod hooks;
+pub mod html;
+#[cfg(target_os = "linux")]
+mod linux;
+pub mod memory;
 pub mod processor;
+pub mod rules;
+pub mod snapshot;
+pub mod system;
 pub mod watcher;
+#[cfg(target_os = "linux")]
+pub mod wayland;
 
-use arboard::{Clipboard as Arboard, ImageData};
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+pub use backend::{ClipboardBackend, OwnerChange, RawImage, Selection};
+pub use file::FileClipboard;
+pub use files::FileEntry;
+pub use memory::MemoryClipboard;
+pub use snapshot::ClipboardSnapshot;
+pub use system::SystemClipboard;
+#[cfg(target_os = "linux")]
+pub use wayland::WaylandClipboard;
 
 use crate::{ClaudeUtilsError, Result};
 
@@ -17,6 +42,22 @@ pub enum ClipboardContent {
         #[serde(skip_serializing_if = "Option::is_none")]
         truncated: Option<bool>,
     },
+    #[serde(rename = "text/html")]
+    Html {
+        data: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        alt_text: Option<String>, // plain-text rendering offered alongside
+        #[serde(skip_serializing_if = "Option::is_none")]
+        truncated: Option<bool>,
+    },
+    #[serde(rename = "text/markdown")]
+    Markdown {
+        data: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        truncated: Option<bool>,
+    },
+    #[serde(rename = "text/uri-list")]
+    Files { files: Vec<FileEntry> },
     #[serde(rename = "image/png")]
     ImagePng {
         #[serde(skip_serializing_if = "Option::is_none")]
@@ -37,6 +78,26 @@ pub enum ClipboardContent {
         height: usize,
         size: usize,
     },
+    /// One exact target, as asked for by MIME type
+    #[serde(rename = "target")]
+    Target {
+        mime: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        data: Option<String>, // text as-is, anything else base64 encoded
+        #[serde(skip_serializing_if = "Option::is_none")]
+        encoding: Option<String>, // "utf-8" or "base64"
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file: Option<String>, // file path if large
+        size: usize,
+    },
+}
+
+/// A target offered by the clipboard owner.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormatInfo {
+    pub mime: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>, // None when unknown without reading the target
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,6 +105,31 @@ pub struct ClipboardMetadata {
     pub timestamp: chrono::DateTime<chrono::Utc>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Selection::is_clipboard")]
+    pub selection: Selection,
+    #[serde(default, skip_serializing_if = "Origin::is_user")]
+    pub origin: Origin,
+    /// Set by plugins in the watch pipeline
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub annotations: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Who put the content on the clipboard.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Origin {
+    /// Copied by the user in some application
+    #[default]
+    User,
+    /// Written by this daemon, e.g. the path watch mode puts back
+    #[serde(rename = "self")]
+    Daemon,
+}
+
+impl Origin {
+    pub fn is_user(&self) -> bool {
+        *self == Origin::User
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -54,87 +140,401 @@ pub struct ClipboardData {
 }
 
 pub struct ClipboardManager {
-    clipboard: Arc<Mutex<Arboard>>,
+    backend: Arc<Mutex<Box<dyn ClipboardBackend>>>,
+    /// Fingerprint of our latest write to each selection
+    own_writes: Mutex<HashMap<Selection, u64>>,
 }
 
 impl ClipboardManager {
+    /// Creates a manager over the system clipboard.
     pub fn new() -> Result<Self> {
-        let clipboard = Arboard::new().map_err(|e| ClaudeUtilsError::Clipboard(e.to_string()))?;
+        Ok(Self::with_backend(SystemClipboard::new()?))
+    }
 
-        Ok(Self {
-            clipboard: Arc::new(Mutex::new(clipboard)),
-        })
+    pub fn with_backend(backend: impl ClipboardBackend + 'static) -> Self {
+        Self {
+            backend: Arc::new(Mutex::new(Box::new(backend))),
+            own_writes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn backend_name(&self) -> &'static str {
+        self.lock()
+            .map(|backend| backend.name())
+            .unwrap_or("unknown")
+    }
+
+    /// Whether the backend has `selection` at all.
+    pub fn supports_selection(&self, selection: Selection) -> bool {
+        self.lock()
+            .map(|backend| backend.supports_selection(selection))
+            .unwrap_or(false)
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Box<dyn ClipboardBackend>>> {
+        self.backend
+            .lock()
+            .map_err(|e| ClaudeUtilsError::Clipboard(format!("Lock error: {e}")))
+    }
+
+    fn lock_for(&self, selection: Selection) -> Result<MutexGuard<'_, Box<dyn ClipboardBackend>>> {
+        let backend = self.lock()?;
+
+        if !backend.supports_selection(selection) {
+            return Err(ClaudeUtilsError::Clipboard(format!(
+                "{} backend has no {selection} selection",
+                backend.name()
+            )));
+        }
+
+        Ok(backend)
     }
 
     pub fn get_content(&self) -> Result<ClipboardData> {
-        let mut clipboard = self
-            .clipboard
+        self.get_content_from(Selection::Clipboard)
+    }
+
+    pub fn get_content_from(&self, selection: Selection) -> Result<ClipboardData> {
+        self.read_content(selection).map(|(_, data)| data)
+    }
+
+    /// `get_content_from`, along with the snapshot it describes, for
+    /// callers that go on to stage what was read.
+    pub fn read_content(&self, selection: Selection) -> Result<(ClipboardSnapshot, ClipboardData)> {
+        let snapshot = self.capture(selection)?;
+        let mut data = self.describe(&snapshot)?;
+
+        data.metadata.origin = self.origin_of(selection, snapshot.fingerprint());
+        Ok((snapshot, data))
+    }
+
+    /// Whether contents with `fingerprint` are the daemon's own last write
+    /// to `selection`, as opposed to something the user copied.
+    pub fn origin_of(&self, selection: Selection, fingerprint: u64) -> Origin {
+        if self.own_write(selection) == Some(fingerprint) {
+            Origin::Daemon
+        } else {
+            Origin::User
+        }
+    }
+
+    fn own_write(&self, selection: Selection) -> Option<u64> {
+        self.own_writes
             .lock()
-            .map_err(|e| ClaudeUtilsError::Clipboard(format!("Lock error: {e}")))?;
+            .ok()
+            .and_then(|writes| writes.get(&selection).copied())
+    }
+
+    /// Remembers what our write to `selection` looks like, so that the
+    /// watcher can tell it from the user's copies.
+    fn record_own_write(&self, backend: &mut Box<dyn ClipboardBackend>, selection: Selection) {
+        let Ok(fingerprint) = backend.fingerprint(selection) else {
+            return;
+        };
+
+        if let Ok(mut writes) = self.own_writes.lock() {
+            writes.insert(selection, fingerprint);
+        }
+    }
+
+    /// Reads the representation `get_content` reports, once, into a
+    /// snapshot that later stages can work from.
+    pub fn capture(&self, selection: Selection) -> Result<ClipboardSnapshot> {
+        self.read_snapshot(selection, None)?.ok_or_else(|| {
+            ClaudeUtilsError::Clipboard(format!("No content in {selection} selection"))
+        })
+    }
+
+    /// Like `capture`, but stops before encoding anything and returns
+    /// `None` while the contents still have the fingerprint `seen`.
+    pub fn capture_if_changed(
+        &self,
+        selection: Selection,
+        seen: u64,
+    ) -> Result<Option<ClipboardSnapshot>> {
+        self.read_snapshot(selection, Some(seen))
+    }
+
+    /// Picks the representation the same way `ClipboardBackend::fingerprint`
+    /// does, so the snapshot's fingerprint matches it.
+    fn read_snapshot(
+        &self,
+        selection: Selection,
+        seen: Option<u64>,
+    ) -> Result<Option<ClipboardSnapshot>> {
+        let mut backend = self.lock_for(selection)?;
+        let unchanged = |fingerprint| seen == Some(fingerprint);
 
         // Try to get image first (more specific)
-        if let Ok(image_data) = clipboard.get_image() {
-            return self.process_image(image_data);
+        if let Ok(image) = backend.get_image(selection) {
+            let fingerprint = backend::hash_image(&image);
+            if unchanged(fingerprint) {
+                return Ok(None);
+            }
+            return Ok(Some(ClipboardSnapshot::new(
+                selection,
+                fingerprint,
+                vec![(backend::MIME_PNG.to_string(), backend::encode_png(&image)?)],
+                Some((image.width, image.height)),
+            )));
+        }
+
+        // Copied files also carry their paths as text, so check them first
+        if let Ok(list) = backend.get_format(selection, backend::MIME_URI_LIST) {
+            if backend::lists_files(&list) {
+                let fingerprint = backend::hash_format(backend::MIME_URI_LIST, &list);
+                if unchanged(fingerprint) {
+                    return Ok(None);
+                }
+                return Ok(Some(ClipboardSnapshot::new(
+                    selection,
+                    fingerprint,
+                    vec![(backend::MIME_URI_LIST.to_string(), list)],
+                    None,
+                )));
+            }
+        }
+
+        // Rich text keeps its structure, with the plain rendering alongside
+        if let Ok(html) = backend.get_format(selection, backend::MIME_HTML) {
+            let fingerprint = backend::hash_format(backend::MIME_HTML, &html);
+            if unchanged(fingerprint) {
+                return Ok(None);
+            }
+            let mut formats = vec![(backend::MIME_HTML.to_string(), html)];
+            if let Ok(text) = backend.get_text(selection) {
+                formats.push((backend::MIME_TEXT.to_string(), text.into_bytes()));
+            }
+            return Ok(Some(ClipboardSnapshot::new(
+                selection,
+                fingerprint,
+                formats,
+                None,
+            )));
         }
 
         // Fall back to text
-        if let Ok(text) = clipboard.get_text() {
-            return Ok(self.process_text(text));
+        if let Ok(text) = backend.get_text(selection) {
+            let fingerprint = backend::hash_format(backend::MIME_TEXT, text.as_bytes());
+            if unchanged(fingerprint) {
+                return Ok(None);
+            }
+            return Ok(Some(ClipboardSnapshot::new(
+                selection,
+                fingerprint,
+                vec![(backend::MIME_TEXT.to_string(), text.into_bytes())],
+                None,
+            )));
         }
 
-        Err(ClaudeUtilsError::Clipboard(
-            "No content in clipboard".to_string(),
-        ))
+        Err(ClaudeUtilsError::Clipboard(format!(
+            "No content in {selection} selection"
+        )))
+    }
+
+    /// What `get_content` would have returned when `snapshot` was taken.
+    pub fn describe(&self, snapshot: &ClipboardSnapshot) -> Result<ClipboardData> {
+        let mut data = if let Some(png) = snapshot.png() {
+            let (width, height) = snapshot.dimensions().unwrap_or_default();
+            self.process_image(png, width, height)
+        } else if let Some(list) = snapshot.uri_list() {
+            self.process_files(&files::parse_uri_list(&list))
+        } else if let Some(html) = snapshot.html() {
+            self.process_html(html, snapshot.text())
+        } else if let Some(text) = snapshot.text() {
+            self.process_text(text)
+        } else {
+            return Err(ClaudeUtilsError::Clipboard(format!(
+                "No content in {} selection",
+                snapshot.selection()
+            )));
+        };
+
+        data.metadata.selection = snapshot.selection();
+        data.metadata.timestamp = snapshot.captured_at();
+        Ok(data)
+    }
+
+    /// Like `describe`, but with the whole text or HTML, never truncated.
+    pub fn describe_in_full(&self, snapshot: &ClipboardSnapshot) -> Result<ClipboardData> {
+        let mut data = self.describe(snapshot)?;
+
+        match &mut data.content {
+            ClipboardContent::Text { data, truncated } if truncated.is_some() => {
+                if let Some(text) = snapshot.text() {
+                    *data = text;
+                    *truncated = None;
+                }
+            }
+            ClipboardContent::Html {
+                data, truncated, ..
+            } if truncated.is_some() => {
+                if let Some(html) = snapshot.html() {
+                    *data = html;
+                    *truncated = None;
+                }
+            }
+            _ => {}
+        }
+
+        Ok(data)
     }
 
     pub fn set_content(&self, content: &ClipboardContent) -> Result<()> {
-        let mut clipboard = self
-            .clipboard
-            .lock()
-            .map_err(|e| ClaudeUtilsError::Clipboard(format!("Lock error: {e}")))?;
+        self.set_content_to(Selection::Clipboard, content)
+    }
+
+    pub fn set_content_to(&self, selection: Selection, content: &ClipboardContent) -> Result<()> {
+        let mut backend = self.lock_for(selection)?;
 
         match content {
-            ClipboardContent::Text { data, .. } => {
-                clipboard
-                    .set_text(data)
-                    .map_err(|e| ClaudeUtilsError::Clipboard(e.to_string()))?;
+            ClipboardContent::Text { data, .. } | ClipboardContent::Markdown { data, .. } => {
+                backend.set_text(selection, data)?;
             }
-            ClipboardContent::ImagePng {
-                data: Some(base64_data),
-                width,
-                height,
-                ..
+            ClipboardContent::Html { data, alt_text, .. } => {
+                let text = alt_text
+                    .clone()
+                    .unwrap_or_else(|| html::html_to_markdown(data));
+
+                backend.set_formats(
+                    selection,
+                    &[
+                        (backend::MIME_HTML.to_string(), data.as_bytes().to_vec()),
+                        (backend::MIME_TEXT.to_string(), text.into_bytes()),
+                    ],
+                )?;
             }
-            | ClipboardContent::ImageJpeg {
-                data: Some(base64_data),
-                width,
-                height,
+            ClipboardContent::ImagePng { data, file, .. }
+            | ClipboardContent::ImageJpeg { data, file, .. } => {
+                // Dimensions come from the encoded image, not the caller
+                let bytes = load_bytes(data.as_deref(), Some("base64"), file.as_deref())?;
+                backend.set_image(selection, &backend::decode_image(&bytes)?)?;
+            }
+            ClipboardContent::Files { files } => {
+                let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+
+                backend.set_formats(
+                    selection,
+                    &[
+                        (
+                            backend::MIME_URI_LIST.to_string(),
+                            files::to_uri_list(&paths).into_bytes(),
+                        ),
+                        (
+                            backend::MIME_TEXT.to_string(),
+                            paths.join("\n").into_bytes(),
+                        ),
+                    ],
+                )?;
+            }
+            ClipboardContent::Target {
+                mime,
+                data,
+                encoding,
+                file,
                 ..
             } => {
-                let bytes = BASE64.decode(base64_data).map_err(|e| {
-                    ClaudeUtilsError::Clipboard(format!("Base64 decode error: {e}"))
-                })?;
-
-                let image_data = ImageData {
-                    width: *width,
-                    height: *height,
-                    bytes: bytes.into(),
-                };
-
-                clipboard
-                    .set_image(image_data)
-                    .map_err(|e| ClaudeUtilsError::Clipboard(e.to_string()))?;
-            }
-            _ => {
-                return Err(ClaudeUtilsError::Clipboard(
-                    "Cannot set clipboard from file reference".to_string(),
-                ));
+                let bytes = load_bytes(data.as_deref(), encoding.as_deref(), file.as_deref())?;
+
+                // Images are decoded so every backend can offer them
+                if mime.starts_with("image/") {
+                    backend.set_image(selection, &backend::decode_image(&bytes)?)?;
+                } else {
+                    backend.set_formats(selection, &[(mime.clone(), bytes)])?;
+                }
             }
         }
 
+        self.record_own_write(&mut backend, selection);
         Ok(())
     }
 
+    /// Notifications of new selection owners, if the backend has them.
+    pub fn owner_changes(
+        &self,
+        selections: &[Selection],
+    ) -> Option<tokio::sync::mpsc::UnboundedReceiver<OwnerChange>> {
+        self.lock().ok()?.owner_changes(selections)
+    }
+
+    /// Changes whenever the contents do; much cheaper than `get_content`.
+    pub fn fingerprint(&self, selection: Selection) -> Result<u64> {
+        self.lock_for(selection)?.fingerprint(selection)
+    }
+
+    /// MIME targets currently offered, without reading any of them.
+    pub fn formats(&self, selection: Selection) -> Result<Vec<String>> {
+        self.lock_for(selection)?.formats(selection)
+    }
+
+    /// Reads a single MIME target straight from the backend.
+    pub fn get_format(&self, selection: Selection, mime: &str) -> Result<Vec<u8>> {
+        self.lock_for(selection)?.get_format(selection, mime)
+    }
+
+    /// Offers several representations of the same content at once.
+    pub fn set_formats(&self, selection: Selection, formats: &[(String, Vec<u8>)]) -> Result<()> {
+        let mut backend = self.lock_for(selection)?;
+        backend.set_formats(selection, formats)?;
+
+        self.record_own_write(&mut backend, selection);
+        Ok(())
+    }
+
+    /// Lists every target on offer, with its size where the backend knows
+    /// it without reading the target.
+    pub fn list_formats(&self, selection: Selection) -> Result<Vec<FormatInfo>> {
+        let mut backend = self.lock_for(selection)?;
+        let mimes = backend.formats(selection)?;
+
+        Ok(mimes
+            .into_iter()
+            .map(|mime| {
+                let size = backend.format_size(selection, &mime);
+                FormatInfo { mime, size }
+            })
+            .collect())
+    }
+
+    /// Reads exactly the bytes the owner offers as `mime`.
+    pub fn read_target(&self, selection: Selection, mime: &str) -> Result<Vec<u8>> {
+        self.lock_for(selection)?.read_target(selection, mime)
+    }
+
+    /// Describes `bytes` read from the `mime` target. Large targets come
+    /// back without data, for the caller to stage.
+    pub fn describe_target(&self, selection: Selection, mime: &str, bytes: &[u8]) -> ClipboardData {
+        let size = bytes.len();
+
+        let (data, encoding) = if size > crate::MAX_INLINE_SIZE {
+            (None, None)
+        } else {
+            match std::str::from_utf8(bytes) {
+                Ok(text) if mime.starts_with("text/") || backend::is_text_mime(mime) => {
+                    (Some(text.to_string()), Some("utf-8".to_string()))
+                }
+                _ => (Some(BASE64.encode(bytes)), Some("base64".to_string())),
+            }
+        };
+
+        ClipboardData {
+            content: ClipboardContent::Target {
+                mime: mime.to_string(),
+                data,
+                encoding,
+                file: None,
+                size,
+            },
+            metadata: ClipboardMetadata {
+                timestamp: chrono::Utc::now(),
+                source: None,
+                origin: Origin::User,
+                selection,
+                annotations: Default::default(),
+            },
+        }
+    }
+
     fn process_text(&self, text: String) -> ClipboardData {
         let truncated = text.len() > crate::MAX_INLINE_SIZE;
         let data = if truncated {
@@ -151,80 +551,102 @@ impl ClipboardManager {
             metadata: ClipboardMetadata {
                 timestamp: chrono::Utc::now(),
                 source: None,
+                origin: Origin::User,
+                selection: Selection::Clipboard,
+                annotations: Default::default(),
             },
         }
     }
 
-    fn process_image(&self, image_data: ImageData<'_>) -> Result<ClipboardData> {
-        use image::{ImageFormat, RgbaImage};
+    fn process_html(&self, html: String, alt_text: Option<String>) -> ClipboardData {
+        let truncated = html.len() > crate::MAX_INLINE_SIZE;
+        let data = if truncated {
+            html.chars().take(crate::MAX_INLINE_SIZE).collect()
+        } else {
+            html
+        };
 
-        // Convert arboard image data to image crate format
-        let img = RgbaImage::from_raw(
-            image_data.width as u32,
-            image_data.height as u32,
-            image_data.bytes.to_vec(),
-        )
-        .ok_or_else(|| {
-            ClaudeUtilsError::ImageProcessing(image::ImageError::Limits(
-                image::error::LimitError::from_kind(image::error::LimitErrorKind::DimensionError),
-            ))
-        })?;
+        ClipboardData {
+            content: ClipboardContent::Html {
+                data,
+                alt_text,
+                truncated: if truncated { Some(true) } else { None },
+            },
+            metadata: ClipboardMetadata {
+                timestamp: chrono::Utc::now(),
+                source: None,
+                origin: Origin::User,
+                selection: Selection::Clipboard,
+                annotations: Default::default(),
+            },
+        }
+    }
 
-        // Detect format and encode
-        let mut png_bytes = Vec::new();
-        img.write_to(&mut std::io::Cursor::new(&mut png_bytes), ImageFormat::Png)?;
+    fn process_files(&self, paths: &[std::path::PathBuf]) -> ClipboardData {
+        ClipboardData {
+            content: ClipboardContent::Files {
+                files: paths.iter().map(|p| FileEntry::from_path(p)).collect(),
+            },
+            metadata: ClipboardMetadata {
+                timestamp: chrono::Utc::now(),
+                source: None,
+                origin: Origin::User,
+                selection: Selection::Clipboard,
+                annotations: Default::default(),
+            },
+        }
+    }
 
+    fn process_image(&self, png_bytes: &[u8], width: usize, height: usize) -> ClipboardData {
         let size = png_bytes.len();
         let (data, file) = if size <= crate::MAX_INLINE_SIZE {
-            (Some(BASE64.encode(&png_bytes)), None)
+            (Some(BASE64.encode(png_bytes)), None)
         } else {
             // Will be handled by file manager
             (None, None)
         };
 
-        Ok(ClipboardData {
+        ClipboardData {
             content: ClipboardContent::ImagePng {
                 data,
                 file,
-                width: image_data.width,
-                height: image_data.height,
+                width,
+                height,
                 size,
             },
             metadata: ClipboardMetadata {
                 timestamp: chrono::Utc::now(),
                 source: None,
+                origin: Origin::User,
+                selection: Selection::Clipboard,
+                annotations: Default::default(),
             },
-        })
+        }
     }
 
     pub fn get_raw_image(&self) -> Result<Vec<u8>> {
-        let mut clipboard = self
-            .clipboard
-            .lock()
-            .map_err(|e| ClaudeUtilsError::Clipboard(format!("Lock error: {e}")))?;
+        self.get_raw_image_from(Selection::Clipboard)
+    }
 
-        let image_data = clipboard
-            .get_image()
-            .map_err(|e| ClaudeUtilsError::Clipboard(e.to_string()))?;
+    pub fn get_raw_image_from(&self, selection: Selection) -> Result<Vec<u8>> {
+        let image = self.lock_for(selection)?.get_image(selection)?;
 
         // Convert to PNG
-        let img = image::RgbaImage::from_raw(
-            image_data.width as u32,
-            image_data.height as u32,
-            image_data.bytes.to_vec(),
-        )
-        .ok_or_else(|| {
-            ClaudeUtilsError::ImageProcessing(image::ImageError::Limits(
-                image::error::LimitError::from_kind(image::error::LimitErrorKind::DimensionError),
-            ))
-        })?;
-
-        let mut png_bytes = Vec::new();
-        img.write_to(
-            &mut std::io::Cursor::new(&mut png_bytes),
-            image::ImageFormat::Png,
-        )?;
-
-        Ok(png_bytes)
+        backend::encode_png(&image)
+    }
+}
+
+/// Resolves inline data (base64 or UTF-8 per `encoding`) or a file reference
+/// to the bytes it stands for.
+fn load_bytes(data: Option<&str>, encoding: Option<&str>, file: Option<&str>) -> Result<Vec<u8>> {
+    match (data, file) {
+        (Some(data), _) if encoding == Some("base64") => BASE64
+            .decode(data)
+            .map_err(|e| ClaudeUtilsError::Clipboard(format!("Base64 decode error: {e}"))),
+        (Some(data), _) => Ok(data.as_bytes().to_vec()),
+        (None, Some(file)) => Ok(std::fs::read(file)?),
+        (None, None) => Err(ClaudeUtilsError::Clipboard(
+            "Content has neither data nor a file".to_string(),
+        )),
     }
 }
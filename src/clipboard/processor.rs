@@ -1,23 +1,38 @@
-use chrono::Local;
-use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use tokio::fs;
-use tokio::sync::mpsc;
-use tracing::{error, info, warn};
-
-use super::{
-    watcher::{platform::DualClipboard, ClipboardEvent},
-    ClipboardContent,
+use tracing::{debug, error, info, warn};
+
+use super::delivery::{self, Delivery, LinkFields, LinkTemplate};
+use super::events::{EventBus, Subscription};
+use super::hooks::{HookConfig, HookRunner, DEFAULT_MAX_CONCURRENT_HOOKS};
+use super::html::html_to_markdown;
+use super::rules::{self, Action, RuleSet};
+use super::watcher::platform::DualClipboard;
+use super::{watcher::ClipboardEvent, ClipboardContent};
+use crate::{
+    file_manager::{FileManager, StagedFile},
+    Result,
 };
-use crate::{file_manager::FileManager, Result};
 
 #[derive(Debug, Clone)]
 pub struct ProcessorConfig {
     pub symlink_dir: PathBuf,
     pub symlink_prefix: String,
+    /// Names the delivered files, after the prefix
+    pub link_template: LinkTemplate,
+    pub delivery: Delivery,
     pub keep_symlinks: usize,
     pub enable_dual_format: bool,
     pub enable_notifications: bool,
+    /// Decides what happens to each copy; the defaults stage images and
+    /// large text
+    pub rules: RuleSet,
+    /// Commands run for every copy a rule handles
+    pub hooks: Vec<HookConfig>,
+    pub max_concurrent_hooks: usize,
 }
 
 impl Default for ProcessorConfig {
@@ -27,9 +42,14 @@ impl Default for ProcessorConfig {
         Self {
             symlink_dir: home.join("Desktop"),
             symlink_prefix: "claude-paste".to_string(),
+            link_template: LinkTemplate::default(),
+            delivery: Delivery::default(),
             keep_symlinks: 5,
             enable_dual_format: true,
             enable_notifications: true,
+            rules: RuleSet::default(),
+            hooks: Vec::new(),
+            max_concurrent_hooks: DEFAULT_MAX_CONCURRENT_HOOKS,
         }
     }
 }
@@ -38,6 +58,13 @@ pub struct ClipboardProcessor {
     config: ProcessorConfig,
     file_manager: Arc<FileManager>,
     clipboard_manager: Arc<super::ClipboardManager>,
+    hooks: HookRunner,
+    /// Files delivered so far, for `{counter}`
+    counter: AtomicU64,
+    /// Hard links and copies delivered so far, oldest first; in those modes
+    /// only these are ever pruned
+    delivered: Mutex<VecDeque<PathBuf>>,
+    output: Option<EventBus>,
 }
 
 impl ClipboardProcessor {
@@ -46,170 +73,229 @@ impl ClipboardProcessor {
         file_manager: Arc<FileManager>,
         clipboard_manager: Arc<super::ClipboardManager>,
     ) -> Self {
+        let hooks = HookRunner::new(config.hooks.clone(), config.max_concurrent_hooks);
+
+        // Cleanup must not leave the links dangling
+        if config.delivery == Delivery::Symlink {
+            file_manager.keep_linked_from(config.symlink_dir.clone());
+        }
+
         Self {
             config,
             file_manager,
             clipboard_manager,
+            hooks,
+            counter: AtomicU64::new(0),
+            delivered: Mutex::new(VecDeque::new()),
+            output: None,
         }
     }
 
-    pub async fn start_processing(self, mut event_rx: mpsc::Receiver<ClipboardEvent>) {
+    /// Publishes every event on `output` once it is processed, with the
+    /// staged and link paths filled in.
+    pub fn with_output(mut self, output: EventBus) -> Self {
+        self.output = Some(output);
+        self
+    }
+
+    pub async fn start_processing(self, mut events: Subscription) {
         info!("Clipboard processor started");
 
-        while let Some(mut event) = event_rx.recv().await {
+        while let Some(mut event) = events.recv().await {
             if let Err(e) = self.process_event(&mut event).await {
                 error!("Failed to process clipboard event: {}", e);
             }
-        }
-    }
-
-    async fn process_event(&self, event: &mut ClipboardEvent) -> Result<()> {
-        match &event.content.content {
-            ClipboardContent::ImagePng { .. } | ClipboardContent::ImageJpeg { .. } => {
-                self.process_image_event(event).await?;
-            }
-            ClipboardContent::Text { data, .. } if data.len() > crate::MAX_INLINE_SIZE => {
-                self.process_large_text_event(event).await?;
-            }
-            _ => {
-                // Small text passes through unchanged
-                debug!("Small text content, no processing needed");
+            if let Some(output) = &self.output {
+                output.publish(event).await;
             }
         }
-
-        Ok(())
     }
 
-    async fn process_image_event(&self, event: &mut ClipboardEvent) -> Result<()> {
-        info!("Processing image clipboard event");
-
-        // Get raw image data
-        let image_data = self.clipboard_manager.get_raw_image()?;
-
-        // Stage the image
-        let format = match &event.content.content {
-            ClipboardContent::ImagePng { .. } => "png",
-            ClipboardContent::ImageJpeg { .. } => "jpeg",
-            _ => unreachable!(),
+    async fn process_event(&self, event: &mut ClipboardEvent) -> Result<()> {
+        let Some(rule) = self.config.rules.find(event) else {
+            debug!("No rule matches, leaving the clipboard as it is");
+            return Ok(());
         };
 
-        let staged = self.file_manager.stage_image(&image_data, format).await?;
-        event.staged_path = Some(staged.path.clone());
-
-        // Create timestamped symlink
-        let symlink_path = self.create_symlink(&staged.path, format).await?;
-        event.symlink_path = Some(symlink_path.clone());
+        info!("Processing clipboard event with rule: {}", rule.name());
+        let is_image = event.snapshot.png().is_some();
 
-        // Set dual clipboard if enabled
-        if self.config.enable_dual_format {
-            let path_str = symlink_path.to_string_lossy();
-
-            #[cfg(target_os = "macos")]
-            {
-                if let Err(e) = DualClipboard::set_dual_content(&path_str, &image_data) {
-                    warn!("Failed to set dual clipboard format: {}", e);
-                    // Fallback to text-only
-                    self.set_text_clipboard(&path_str)?;
-                } else {
-                    info!("Set dual clipboard: text path + original image");
-                }
-            }
+        // Staged from the snapshot, even if the clipboard has moved on
+        if rule.stages() {
+            let (data, extension) = rules::payload(event, rule.convert_to())?;
+            let staged = if is_image {
+                self.file_manager.stage_image(&data, extension).await?
+            } else {
+                self.file_manager.stage_data(&data, extension).await?
+            };
+            event.staged_path = Some(staged.path.clone());
 
-            #[cfg(not(target_os = "macos"))]
-            {
-                // On other platforms, just set text
-                self.set_text_clipboard(&path_str)?;
+            if rule.has(Action::Symlink) {
+                let symlink_path = self.create_symlink(event, &staged, extension).await?;
+                event.symlink_path = Some(symlink_path);
+                self.cleanup_old_symlinks().await?;
             }
         }
 
-        // Clean up old symlinks
-        self.cleanup_old_symlinks().await?;
-
-        // Show notification if enabled
-        if self.config.enable_notifications {
-            let notification_path = symlink_path.to_string_lossy();
-            self.show_notification("Image ready for Claude Code", &notification_path);
-        }
-
-        info!("Image processed: {}", symlink_path.display());
-        Ok(())
-    }
-
-    async fn process_large_text_event(&self, event: &mut ClipboardEvent) -> Result<()> {
-        info!("Processing large text clipboard event");
-
-        if let ClipboardContent::Text { data, .. } = &event.content.content {
-            // Stage the text
-            let staged = self.file_manager.stage_text(data).await?;
-            event.staged_path = Some(staged.path.clone());
-
-            // Create symlink
-            let symlink_path = self.create_symlink(&staged.path, "txt").await?;
-            event.symlink_path = Some(symlink_path.clone());
+        let path = event
+            .symlink_path
+            .as_ref()
+            .or(event.staged_path.as_ref())
+            .map(|path| path.to_string_lossy().to_string());
 
-            // Update clipboard with path
-            let path_str = symlink_path.to_string_lossy();
-            self.set_text_clipboard(&path_str)?;
+        let replacement = self.hooks.run(rule.name(), event).await;
 
-            // Clean up old symlinks
-            self.cleanup_old_symlinks().await?;
+        // Without dual format a copied image stays on the clipboard as it is
+        let rewrite = rule.has(Action::Rewrite) && (self.config.enable_dual_format || !is_image);
+        if let Some(path) = path.as_deref().filter(|_| rewrite) {
+            self.rewrite_clipboard(event, replacement.as_deref().unwrap_or(path))?;
+        }
 
-            info!("Large text processed: {}", symlink_path.display());
+        if rule.has(Action::Notify) && self.config.enable_notifications {
+            let title = if is_image {
+                "Image ready for Claude Code"
+            } else {
+                "Clipboard content ready for Claude Code"
+            };
+            self.show_notification(title, path.as_deref().unwrap_or_default());
         }
 
+        if let Some(path) = &path {
+            info!("Clipboard content processed: {}", path);
+        }
         Ok(())
     }
 
-    async fn create_symlink(&self, target: &Path, extension: &str) -> Result<PathBuf> {
-        // Generate timestamped filename
-        let timestamp = Local::now().format("%Y%m%d-%H%M%S");
-        let filename = format!("{}-{}.{}", self.config.symlink_prefix, timestamp, extension);
-        let symlink_path = self.config.symlink_dir.join(&filename);
-
-        // Create symlink
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::symlink;
-            symlink(target, &symlink_path)?;
+    /// Puts `text`, the path or what a hook made of it, on the clipboard in
+    /// place of the copied content.
+    fn rewrite_clipboard(&self, event: &ClipboardEvent, text: &str) -> Result<()> {
+        let selection = event.snapshot.selection();
+
+        // Image apps can still paste the picture itself
+        let dual_image = event
+            .snapshot
+            .png()
+            .filter(|_| self.config.enable_dual_format && selection.is_clipboard());
+        if let Some(image_data) = dual_image {
+            match DualClipboard::set_dual_content(&self.clipboard_manager, text, image_data) {
+                Ok(()) => {
+                    info!("Set dual clipboard: text path + original image");
+                    return Ok(());
+                }
+                Err(e) => {
+                    // Fallback to text-only
+                    warn!("Failed to set dual clipboard format: {}", e);
+                }
+            }
         }
 
-        #[cfg(windows)]
-        {
-            use std::os::windows::fs::symlink_file;
-            symlink_file(target, &symlink_path)?;
+        self.clipboard_manager.set_content_to(
+            selection,
+            &ClipboardContent::Text {
+                data: text.to_string(),
+                truncated: None,
+            },
+        )
+    }
+
+    /// Delivers the staged file to the link directory under a name from the
+    /// template, and as the "latest" file.
+    async fn create_symlink(
+        &self,
+        event: &ClipboardEvent,
+        staged: &StagedFile,
+        extension: &str,
+    ) -> Result<PathBuf> {
+        let fields = LinkFields {
+            hash: staged.hash.clone(),
+            counter: self.counter.fetch_add(1, Ordering::Relaxed) + 1,
+            slug: Self::slug(event),
+        };
+        let name = format!(
+            "{}-{}",
+            self.config.symlink_prefix,
+            self.config.link_template.render(&fields)
+        );
+        let delivery = self.config.delivery;
+
+        let link_path = delivery::deliver(
+            &staged.path,
+            &self.config.symlink_dir,
+            &name,
+            extension,
+            delivery,
+        )
+        .await?;
+        if delivery != Delivery::Symlink {
+            if let Ok(mut delivered) = self.delivered.lock() {
+                // A name freed by the user and taken again is listed once
+                delivered.retain(|path| *path != link_path);
+                delivered.push_back(link_path.clone());
+            }
         }
 
-        // Also create a "latest" symlink for convenience
+        // Also create a "latest" link for convenience
         let latest_name = format!("{}.{}", self.config.symlink_prefix, extension);
         let latest_path = self.config.symlink_dir.join(&latest_name);
+        delivery::replace(&staged.path, &latest_path, delivery).await?;
 
-        // Remove old latest symlink if exists
-        let _ = fs::remove_file(&latest_path).await;
+        Ok(link_path)
+    }
 
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::symlink;
-            symlink(target, &latest_path)?;
+    fn slug(event: &ClipboardEvent) -> String {
+        let snapshot = &event.snapshot;
+        if let Some((width, height)) = snapshot.dimensions() {
+            return format!("image-{width}x{height}");
         }
 
-        #[cfg(windows)]
-        {
-            use std::os::windows::fs::symlink_file;
-            symlink_file(target, &latest_path)?;
+        let slug = snapshot
+            .text()
+            .or_else(|| snapshot.html().map(|html| html_to_markdown(&html)))
+            .map(|text| delivery::slugify(&text))
+            .unwrap_or_default();
+        if slug.is_empty() {
+            "clip".to_string()
+        } else {
+            slug
         }
-
-        Ok(symlink_path)
     }
 
     async fn cleanup_old_symlinks(&self) -> Result<()> {
-        let pattern = format!("{}-*", self.config.symlink_prefix);
+        // Hard links and copies look like any other file, so files the user
+        // happened to give the prefix must not be taken for them
+        let old: Vec<PathBuf> = match self.config.delivery {
+            Delivery::Symlink => self.old_symlinks().await?,
+            Delivery::Hardlink | Delivery::Copy => match self.delivered.lock() {
+                Ok(mut delivered) => {
+                    let excess = delivered.len().saturating_sub(self.config.keep_symlinks);
+                    delivered.drain(..excess).collect()
+                }
+                Err(_) => Vec::new(),
+            },
+        };
+
+        for path in old {
+            if let Err(e) = fs::remove_file(&path).await {
+                warn!("Failed to remove old symlink: {}", e);
+            } else {
+                debug!("Removed old symlink: {}", path.display());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Symlinks in the link directory beyond `keep_symlinks`, including those
+    /// made before a restart.
+    async fn old_symlinks(&self) -> Result<Vec<PathBuf>> {
+        let pattern = format!("{}-", self.config.symlink_prefix);
         let mut entries = fs::read_dir(&self.config.symlink_dir).await?;
         let mut symlinks = Vec::new();
 
         while let Some(entry) = entries.next_entry().await? {
             let path = entry.path();
             if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                if name.starts_with(&pattern) && name.contains('-') {
+                if name.starts_with(&pattern) {
                     if let Ok(metadata) = entry.metadata().await {
                         if metadata.file_type().is_symlink() {
                             if let Ok(modified) = metadata.modified() {
@@ -222,25 +308,13 @@ impl ClipboardProcessor {
         }
 
         // Sort by modification time (newest first)
-        symlinks.sort_by(|a, b| b.1.cmp(&a.1));
+        symlinks.sort_by_key(|b| std::cmp::Reverse(b.1));
 
-        // Remove old symlinks beyond keep limit
-        for (path, _) in symlinks.into_iter().skip(self.config.keep_symlinks) {
-            if let Err(e) = fs::remove_file(&path).await {
-                warn!("Failed to remove old symlink: {}", e);
-            } else {
-                debug!("Removed old symlink: {}", path.display());
-            }
-        }
-
-        Ok(())
-    }
-
-    fn set_text_clipboard(&self, text: &str) -> Result<()> {
-        self.clipboard_manager.set_content(&ClipboardContent::Text {
-            data: text.to_string(),
-            truncated: None,
-        })
+        Ok(symlinks
+            .into_iter()
+            .skip(self.config.keep_symlinks)
+            .map(|(path, _)| path)
+            .collect())
     }
 
     fn show_notification(&self, title: &str, body: &str) {
@@ -258,7 +332,7 @@ impl ClipboardProcessor {
             use std::process::Command;
             let _ = Command::new("notify-send")
                 .arg("Claude-Utils")
-                .arg(&format!("{}\n{}", title, body))
+                .arg(format!("{title}\n{body}"))
                 .output();
         }
 
@@ -269,5 +343,3 @@ impl ClipboardProcessor {
         }
     }
 }
-
-use tracing::debug;
@@ -0,0 +1,291 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+use super::backend::is_text_mime;
+use super::filter::mime_matches;
+use super::html::html_to_markdown;
+use super::watcher::ClipboardEvent;
+use super::Selection;
+use crate::{ClaudeUtilsError, Result};
+
+/// What the processor does with content a rule matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Write the content to the staging directory
+    Stage,
+    /// Link the staged file from the symlink directory (stages it first)
+    Symlink,
+    /// Put the path back on the clipboard in place of the content
+    Rewrite,
+    /// Do nothing with the copy, which also keeps later rules from acting
+    /// on it; must be the rule's only action
+    Leave,
+    /// Re-encode before staging, into the rule's `convert_to` format
+    Convert,
+    /// Show a desktop notification
+    Notify,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConvertTo {
+    Png,
+    Jpeg,
+    Webp,
+    /// Rich text rendered as Markdown
+    Markdown,
+    /// The plain-text alternative of rich text
+    Text,
+}
+
+/// Conditions a copy must meet; empty ones match anything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleMatch {
+    /// MIME types; `image/*` matches every image
+    pub types: Vec<String>,
+    /// Bounds in bytes on the content as captured
+    pub min_size: Option<usize>,
+    pub max_size: Option<usize>,
+    /// A regular expression the text must match
+    pub pattern: Option<String>,
+    /// Selections the copy must come from
+    pub source: Vec<Selection>,
+}
+
+/// One `[[watch.rules]]` entry of the config file.
+///
+/// ```toml
+/// [[watch.rules]]
+/// name = "screenshots as jpeg"
+/// match = { types = ["image/*"], min_size = 1048576 }
+/// actions = ["convert", "stage", "symlink", "rewrite", "notify"]
+/// convert_to = "jpeg"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleConfig {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default, rename = "match")]
+    pub matches: RuleMatch,
+    #[serde(default)]
+    pub actions: Vec<Action>,
+    #[serde(default)]
+    pub convert_to: Option<ConvertTo>,
+}
+
+fn stages(actions: &[Action]) -> bool {
+    actions
+        .iter()
+        .any(|a| matches!(a, Action::Stage | Action::Symlink | Action::Rewrite))
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    name: String,
+    config: RuleConfig,
+    pattern: Option<Regex>,
+}
+
+impl Rule {
+    fn new(index: usize, config: RuleConfig) -> Result<Self> {
+        let name = config
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("rule {}", index + 1));
+        let invalid = |message: String| ClaudeUtilsError::Config(format!("{name}: {message}"));
+
+        let actions = &config.actions;
+        if actions.contains(&Action::Leave) && actions.iter().any(|a| *a != Action::Leave) {
+            return Err(invalid(
+                "`leave` cannot be combined with other actions".to_string(),
+            ));
+        }
+        if actions.contains(&Action::Convert) && !stages(actions) {
+            return Err(invalid(
+                "`convert` needs `stage`, `symlink` or `rewrite`".to_string(),
+            ));
+        }
+        if actions.contains(&Action::Convert) != config.convert_to.is_some() {
+            return Err(invalid(
+                "`convert` needs `convert_to`, and `convert_to` needs `convert`".to_string(),
+            ));
+        }
+
+        let pattern = config
+            .matches
+            .pattern
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| invalid(format!("invalid pattern: {e}")))?;
+
+        Ok(Self {
+            name,
+            config,
+            pattern,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn has(&self, action: Action) -> bool {
+        self.config.actions.contains(&action)
+    }
+
+    /// Whether the content has to be written to the staging directory.
+    pub fn stages(&self) -> bool {
+        stages(&self.config.actions)
+    }
+
+    pub fn convert_to(&self) -> Option<ConvertTo> {
+        self.config.convert_to
+    }
+
+    pub fn matches(&self, event: &ClipboardEvent) -> bool {
+        let matches = &self.config.matches;
+        let Some((mime, bytes)) = event.snapshot.formats().first() else {
+            return false;
+        };
+
+        if !matches.source.is_empty() && !matches.source.contains(&event.snapshot.selection()) {
+            return false;
+        }
+        if !matches.types.is_empty() && !matches.types.iter().any(|t| mime_matches(t, mime)) {
+            return false;
+        }
+        if matches.min_size.is_some_and(|min| bytes.len() < min) {
+            return false;
+        }
+        if matches.max_size.is_some_and(|max| bytes.len() > max) {
+            return false;
+        }
+        if let Some(pattern) = &self.pattern {
+            return event
+                .snapshot
+                .text()
+                .is_some_and(|text| pattern.is_match(&text));
+        }
+
+        true
+    }
+}
+
+/// The processor's rules, tried in order; the first match decides.
+#[derive(Debug, Clone)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    pub fn new(configs: Vec<RuleConfig>) -> Result<Self> {
+        let rules = configs
+            .into_iter()
+            .enumerate()
+            .map(|(index, config)| Rule::new(index, config))
+            .collect::<Result<_>>()?;
+
+        Ok(Self { rules })
+    }
+
+    /// What watch mode has always done: images and text too large to
+    /// inline are staged, linked and replaced by their path.
+    pub fn default_configs() -> Vec<RuleConfig> {
+        let stage_and_rewrite = vec![Action::Stage, Action::Symlink, Action::Rewrite];
+
+        vec![
+            RuleConfig {
+                name: Some("images".to_string()),
+                matches: RuleMatch {
+                    types: vec!["image/*".to_string()],
+                    source: vec![Selection::Clipboard],
+                    ..Default::default()
+                },
+                actions: [stage_and_rewrite.as_slice(), &[Action::Notify]].concat(),
+                convert_to: None,
+            },
+            RuleConfig {
+                name: Some("large text".to_string()),
+                matches: RuleMatch {
+                    types: vec!["text/plain".to_string()],
+                    min_size: Some(crate::MAX_INLINE_SIZE + 1),
+                    source: vec![Selection::Clipboard],
+                    ..Default::default()
+                },
+                actions: stage_and_rewrite,
+                convert_to: None,
+            },
+        ]
+    }
+
+    pub fn find(&self, event: &ClipboardEvent) -> Option<&Rule> {
+        self.rules.iter().find(|rule| rule.matches(event))
+    }
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self::new(Self::default_configs()).expect("built-in rules are valid")
+    }
+}
+
+/// The content to stage for `event`, converted as asked, with the file
+/// extension to stage it under.
+pub(crate) fn payload(
+    event: &ClipboardEvent,
+    convert_to: Option<ConvertTo>,
+) -> Result<(Vec<u8>, &'static str)> {
+    let snapshot = &event.snapshot;
+    let unconvertible = |to: ConvertTo| {
+        ClaudeUtilsError::Clipboard(format!("Cannot convert this content to {to:?}"))
+    };
+
+    match convert_to {
+        None => {
+            let (mime, bytes) = snapshot
+                .formats()
+                .first()
+                .ok_or_else(|| ClaudeUtilsError::Clipboard("Snapshot is empty".to_string()))?;
+            let extension = if is_text_mime(mime) {
+                "txt"
+            } else {
+                mime_guess::get_mime_extensions_str(mime)
+                    .and_then(|extensions| extensions.first())
+                    .copied()
+                    .unwrap_or("bin")
+            };
+
+            Ok((bytes.clone(), extension))
+        }
+        Some(to @ (ConvertTo::Png | ConvertTo::Jpeg | ConvertTo::Webp)) => {
+            let png = snapshot.png().ok_or_else(|| unconvertible(to))?;
+            let image = image::load_from_memory(png)?;
+            let (image, format, extension) = match to {
+                ConvertTo::Png => (image, image::ImageFormat::Png, "png"),
+                // JPEG has no alpha channel
+                ConvertTo::Jpeg => (image.to_rgb8().into(), image::ImageFormat::Jpeg, "jpeg"),
+                _ => (image, image::ImageFormat::WebP, "webp"),
+            };
+
+            let mut bytes = Vec::new();
+            image.write_to(&mut Cursor::new(&mut bytes), format)?;
+            Ok((bytes, extension))
+        }
+        Some(ConvertTo::Markdown) => {
+            let html = snapshot
+                .html()
+                .ok_or_else(|| unconvertible(ConvertTo::Markdown))?;
+            Ok((html_to_markdown(&html).into_bytes(), "md"))
+        }
+        Some(ConvertTo::Text) => {
+            let text = snapshot
+                .text()
+                .ok_or_else(|| unconvertible(ConvertTo::Text))?;
+            Ok((text.into_bytes(), "txt"))
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::fmt;
+use std::sync::Arc;
+
+use super::backend::{is_text_mime, Selection, MIME_HTML, MIME_PNG, MIME_TEXT, MIME_URI_LIST};
+use super::ClipboardContent;
+
+/// The clipboard as it was read at one moment: the bytes of every format
+/// captured, so later stages never go back to a clipboard that may have
+/// moved on in the meantime.
+///
+/// Clones share the same bytes, and nothing can change them.
+#[derive(Clone)]
+pub struct ClipboardSnapshot {
+    selection: Selection,
+    captured_at: chrono::DateTime<chrono::Utc>,
+    fingerprint: u64,
+    formats: Arc<[(String, Vec<u8>)]>,
+    dimensions: Option<(usize, usize)>,
+}
+
+impl ClipboardSnapshot {
+    pub(crate) fn new(
+        selection: Selection,
+        fingerprint: u64,
+        formats: Vec<(String, Vec<u8>)>,
+        dimensions: Option<(usize, usize)>,
+    ) -> Self {
+        Self {
+            selection,
+            captured_at: chrono::Utc::now(),
+            fingerprint,
+            formats: formats.into(),
+            dimensions,
+        }
+    }
+
+    /// This snapshot holding `content` in place of what was captured, for
+    /// text-like content only. It still has the captured fingerprint.
+    pub(crate) fn rewritten(&self, content: &ClipboardContent) -> Option<Self> {
+        let formats = match content {
+            ClipboardContent::Text { data, .. } | ClipboardContent::Markdown { data, .. } => {
+                vec![(MIME_TEXT.to_string(), data.clone().into_bytes())]
+            }
+            ClipboardContent::Html { data, alt_text, .. } => {
+                let mut formats = vec![(MIME_HTML.to_string(), data.clone().into_bytes())];
+                if let Some(text) = alt_text {
+                    formats.push((MIME_TEXT.to_string(), text.clone().into_bytes()));
+                }
+                formats
+            }
+            _ => return None,
+        };
+
+        Some(Self {
+            formats: formats.into(),
+            dimensions: None,
+            ..self.clone()
+        })
+    }
+
+    pub fn selection(&self) -> Selection {
+        self.selection
+    }
+
+    pub fn captured_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.captured_at
+    }
+
+    /// What `ClipboardManager::fingerprint` said about the contents this
+    /// was captured from.
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// Every captured format, richest first.
+    pub fn formats(&self) -> &[(String, Vec<u8>)] {
+        &self.formats
+    }
+
+    /// The bytes captured for `mime`, treating text names as one.
+    pub fn get(&self, mime: &str) -> Option<&[u8]> {
+        self.formats
+            .iter()
+            .find(|(offered, _)| offered == mime || (is_text_mime(offered) && is_text_mime(mime)))
+            .map(|(_, bytes)| bytes.as_slice())
+    }
+
+    /// The full text, never truncated.
+    pub fn text(&self) -> Option<String> {
+        self.get("text/plain")
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    pub fn html(&self) -> Option<String> {
+        self.get(MIME_HTML)
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    pub fn uri_list(&self) -> Option<String> {
+        self.get(MIME_URI_LIST)
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    /// The image as PNG, encoded once when it was captured.
+    pub fn png(&self) -> Option<&[u8]> {
+        self.get(MIME_PNG)
+    }
+
+    /// Width and height, when the snapshot holds an image.
+    pub fn dimensions(&self) -> Option<(usize, usize)> {
+        self.dimensions
+    }
+}
+
+impl fmt::Debug for ClipboardSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Sizes rather than bytes; a screenshot would drown the log
+        let formats: Vec<(&str, usize)> = self
+            .formats
+            .iter()
+            .map(|(mime, bytes)| (mime.as_str(), bytes.len()))
+            .collect();
+
+        f.debug_struct("ClipboardSnapshot")
+            .field("selection", &self.selection)
+            .field("captured_at", &self.captured_at)
+            .field("fingerprint", &self.fingerprint)
+            .field("formats", &formats)
+            .field("dimensions", &self.dimensions)
+            .finish()
+    }
+}
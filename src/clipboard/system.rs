@@ -0,0 +1,313 @@
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+use arboard::{ClearExtLinux, GetExtLinux, LinuxClipboardKind, SetExtLinux};
+use arboard::{Clipboard as Arboard, ImageData};
+
+use super::backend::{
+    decode_image, encode_png, is_text_mime, unsupported_format, ClipboardBackend, RawImage,
+    Selection, MIME_HTML, MIME_PNG, MIME_URI_LIST,
+};
+use super::files::{parse_uri_list, to_uri_list};
+use crate::{ClaudeUtilsError, Result};
+#[cfg(target_os = "linux")]
+use {super::backend::OwnerChange, tokio::sync::mpsc};
+
+/// The desktop clipboard, via arboard.
+pub struct SystemClipboard {
+    clipboard: Arboard,
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    wait: bool,
+}
+
+impl SystemClipboard {
+    pub fn new() -> Result<Self> {
+        let clipboard = Arboard::new().map_err(clipboard_error)?;
+
+        Ok(Self {
+            clipboard,
+            wait: false,
+        })
+    }
+
+    /// Makes every write block until another application takes the
+    /// selection over, for a process whose only job is to keep it alive.
+    /// Only X11 and Wayland drop contents when their owner exits; elsewhere
+    /// writes return straight away.
+    pub fn wait_until_replaced(mut self) -> Self {
+        self.wait = true;
+        self
+    }
+
+    /// Whether a desktop session is reachable. Only X11/Wayland hosts can be
+    /// without one; elsewhere the clipboard is always there.
+    pub fn is_available() -> bool {
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            ["DISPLAY", "WAYLAND_DISPLAY"]
+                .iter()
+                .any(|var| std::env::var_os(var).is_some_and(|v| !v.is_empty()))
+        }
+
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        {
+            true
+        }
+    }
+}
+
+// X11 and Wayland route every operation to the requested selection
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+impl SystemClipboard {
+    fn get(&mut self, selection: Selection) -> arboard::Get<'_> {
+        self.clipboard.get().clipboard(linux_kind(selection))
+    }
+
+    fn set(&mut self, selection: Selection) -> arboard::Set<'_> {
+        let set = self.clipboard.set().clipboard(linux_kind(selection));
+
+        if self.wait {
+            set.wait()
+        } else {
+            set
+        }
+    }
+
+    fn clear(&mut self, selection: Selection) -> Result<()> {
+        self.clipboard
+            .clear_with()
+            .clipboard(linux_kind(selection))
+            .map_err(clipboard_error)
+    }
+}
+
+// Elsewhere there is only the clipboard; `ClipboardManager` rejects other
+// selections before they get here
+#[cfg(not(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+)))]
+impl SystemClipboard {
+    fn get(&mut self, _selection: Selection) -> arboard::Get<'_> {
+        self.clipboard.get()
+    }
+
+    fn set(&mut self, _selection: Selection) -> arboard::Set<'_> {
+        self.clipboard.set()
+    }
+
+    fn clear(&mut self, _selection: Selection) -> Result<()> {
+        self.clipboard.clear().map_err(clipboard_error)
+    }
+}
+
+impl ClipboardBackend for SystemClipboard {
+    fn name(&self) -> &'static str {
+        "system"
+    }
+
+    fn supports_selection(&self, selection: Selection) -> bool {
+        selection.is_clipboard()
+            || cfg!(all(
+                unix,
+                not(any(
+                    target_os = "macos",
+                    target_os = "android",
+                    target_os = "emscripten"
+                ))
+            ))
+    }
+
+    fn get_text(&mut self, selection: Selection) -> Result<String> {
+        self.get(selection).text().map_err(clipboard_error)
+    }
+
+    fn set_text(&mut self, selection: Selection, text: &str) -> Result<()> {
+        self.set(selection).text(text).map_err(clipboard_error)
+    }
+
+    fn get_image(&mut self, selection: Selection) -> Result<RawImage> {
+        let image_data = self.get(selection).image().map_err(clipboard_error)?;
+
+        Ok(RawImage {
+            width: image_data.width,
+            height: image_data.height,
+            bytes: image_data.bytes.into_owned(),
+        })
+    }
+
+    fn set_image(&mut self, selection: Selection, image: &RawImage) -> Result<()> {
+        let image_data = ImageData {
+            width: image.width,
+            height: image.height,
+            bytes: image.bytes.as_slice().into(),
+        };
+
+        self.set(selection)
+            .image(image_data)
+            .map_err(clipboard_error)
+    }
+
+    fn formats(&mut self, selection: Selection) -> Result<Vec<String>> {
+        #[cfg(target_os = "linux")]
+        {
+            super::linux::targets(selection)
+        }
+
+        // Elsewhere we can only tell which of arboard's targets are present
+        #[cfg(not(target_os = "linux"))]
+        {
+            use super::backend::MIME_TEXT;
+
+            Ok([MIME_PNG, MIME_URI_LIST, MIME_HTML, MIME_TEXT]
+                .into_iter()
+                .filter(|mime| self.get_format(selection, mime).is_ok())
+                .map(str::to_string)
+                .collect())
+        }
+    }
+
+    fn get_format(&mut self, selection: Selection, mime: &str) -> Result<Vec<u8>> {
+        if is_text_mime(mime) {
+            return self.get_text(selection).map(String::into_bytes);
+        }
+
+        if mime == MIME_PNG {
+            return encode_png(&self.get_image(selection)?);
+        }
+
+        if mime == MIME_HTML {
+            let html = self.get(selection).html().map_err(clipboard_error)?;
+            return Ok(html.into_bytes());
+        }
+
+        if mime == MIME_URI_LIST {
+            let paths = self.get(selection).file_list().map_err(clipboard_error)?;
+            return Ok(to_uri_list(&paths).into_bytes());
+        }
+
+        // arboard only exposes the targets it knows how to convert; on
+        // X11/Wayland we can ask the owner for anything else directly
+        #[cfg(target_os = "linux")]
+        {
+            super::linux::read(selection, mime)
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            Err(unsupported_format(self.name(), mime))
+        }
+    }
+
+    fn read_target(&mut self, selection: Selection, mime: &str) -> Result<Vec<u8>> {
+        #[cfg(target_os = "linux")]
+        {
+            super::linux::read(selection, mime)
+        }
+
+        // Elsewhere arboard's conversions are all there is
+        #[cfg(not(target_os = "linux"))]
+        {
+            self.get_format(selection, mime)
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn format_size(&mut self, selection: Selection, mime: &str) -> Option<usize> {
+        super::linux::size(selection, mime).ok()
+    }
+
+    fn set_formats(&mut self, selection: Selection, formats: &[(String, Vec<u8>)]) -> Result<()> {
+        // On X11/Wayland we can own the selection ourselves and offer all of
+        // them, e.g. a path for terminals alongside the image it points at
+        #[cfg(target_os = "linux")]
+        if needs_own_owner(formats) {
+            return super::linux::offer(selection, formats, self.wait);
+        }
+
+        // arboard can only own one representation at a time, so prefer
+        // the richest one we were given
+        if let Some((_, bytes)) = formats.iter().find(|(mime, _)| mime == MIME_PNG) {
+            return self.set_image(selection, &decode_image(bytes)?);
+        }
+
+        if let Some((_, list)) = formats.iter().find(|(mime, _)| mime == MIME_URI_LIST) {
+            let paths = parse_uri_list(&String::from_utf8_lossy(list));
+            return self
+                .set(selection)
+                .file_list(&paths)
+                .map_err(clipboard_error);
+        }
+
+        let text = formats
+            .iter()
+            .find(|(mime, _)| is_text_mime(mime))
+            .map(|(_, bytes)| String::from_utf8_lossy(bytes));
+
+        if let Some((_, html)) = formats.iter().find(|(mime, _)| mime == MIME_HTML) {
+            return self
+                .set(selection)
+                .html(String::from_utf8_lossy(html), text)
+                .map_err(clipboard_error);
+        }
+
+        if let Some(text) = text {
+            return self.set_text(selection, &text);
+        }
+
+        match formats.first() {
+            Some((mime, _)) => Err(unsupported_format(self.name(), mime)),
+            None => self.clear(selection),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn owner_changes(
+        &mut self,
+        selections: &[Selection],
+    ) -> Option<mpsc::UnboundedReceiver<OwnerChange>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        match super::linux::watch_owners(selections, tx) {
+            Ok(()) => Some(rx),
+            Err(e) => {
+                tracing::debug!("Selection owner notifications unavailable: {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// Whether `formats` is more than arboard can offer: anything beyond text,
+/// HTML and file lists, or PNG alongside something else.
+#[cfg(target_os = "linux")]
+fn needs_own_owner(formats: &[(String, Vec<u8>)]) -> bool {
+    let arboard_offers =
+        |mime: &str| is_text_mime(mime) || mime == MIME_HTML || mime == MIME_URI_LIST;
+
+    match formats {
+        [] => false,
+        [(mime, _)] => !arboard_offers(mime) && mime != MIME_PNG,
+        _ => !formats.iter().all(|(mime, _)| arboard_offers(mime)),
+    }
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+fn linux_kind(selection: Selection) -> LinuxClipboardKind {
+    match selection {
+        Selection::Clipboard => LinuxClipboardKind::Clipboard,
+        Selection::Primary => LinuxClipboardKind::Primary,
+        Selection::Secondary => LinuxClipboardKind::Secondary,
+    }
+}
+
+fn clipboard_error(e: arboard::Error) -> ClaudeUtilsError {
+    ClaudeUtilsError::Clipboard(e.to_string())
+}
@@ -1,16 +1,27 @@
+use serde::Serialize;
+use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
-use tokio::sync::{mpsc, RwLock};
-use tokio::time::{interval, MissedTickBehavior};
+use tokio::sync::{watch, RwLock};
+use tokio::time::sleep;
 use tracing::{debug, error, info, warn};
 
-use super::{ClipboardContent, ClipboardData, ClipboardManager};
+use super::events::EventBus;
+use super::filter::WatchFilter;
+use super::{
+    ClipboardContent, ClipboardData, ClipboardManager, ClipboardSnapshot, Origin, Selection,
+};
+use crate::plugins::{PluginHost, Verdict};
 use crate::Result;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct WatchedContent {
-    pub content_hash: String,
+    /// Increases by one with every change the watcher reports, across all
+    /// selections
+    pub sequence: u64,
+    pub fingerprint: u64,
     pub timestamp: SystemTime,
     pub content_type: ContentType,
 }
@@ -18,157 +29,522 @@ pub struct WatchedContent {
 #[derive(Debug, Clone, PartialEq)]
 pub enum ContentType {
     Text(usize),                 // size
+    Html(usize),                 // size
+    Files(usize),                // count
     Image(String, usize, usize), // format, width, height
+    Target(String, usize),       // mime, size
+    Filtered,                    // withheld by the watch filter or a pause
 }
 
 #[derive(Debug, Clone)]
 pub struct ClipboardEvent {
+    /// The `WatchedContent` sequence number of this change
+    pub sequence: u64,
     pub content: ClipboardData,
+    /// The bytes `content` was made from; act on these, not a fresh read
+    pub snapshot: ClipboardSnapshot,
+    /// Every MIME type the new owner offers, in its order
+    pub mime_types: Vec<String>,
     pub staged_path: Option<PathBuf>,
     pub symlink_path: Option<PathBuf>,
 }
 
+/// Each idle poll waits this much longer than the last, up to the idle interval.
+const IDLE_BACKOFF: f64 = 1.5;
+
+/// How the watcher learns about changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchMode {
+    /// Waiting for a client to ask for changes before reading anything
+    Idle,
+    Starting,
+    /// The backend reports new selection owners
+    Notifications,
+    Polling,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchStatus {
+    pub paused: bool,
+    pub mode: WatchMode,
+    /// The current poll interval, while polling
+    pub poll_interval_ms: Option<u64>,
+    /// The sequence number of the latest reported change
+    pub sequence: u64,
+}
+
+struct ControlState {
+    active: watch::Sender<bool>,
+    paused: watch::Sender<bool>,
+    mode: Mutex<WatchMode>,
+    interval_ms: AtomicU64,
+    sequence: AtomicU64,
+}
+
+/// Pauses, resumes and inspects a running watcher. Clones control the same
+/// watcher.
+#[derive(Clone)]
+pub struct WatchControl {
+    state: Arc<ControlState>,
+}
+
+impl WatchControl {
+    fn new() -> Self {
+        Self {
+            state: Arc::new(ControlState {
+                active: watch::channel(true).0,
+                paused: watch::channel(false).0,
+                mode: Mutex::new(WatchMode::Starting),
+                interval_ms: AtomicU64::new(0),
+                sequence: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Starts a watcher made with `ClipboardWatcher::on_demand`. Does
+    /// nothing once it runs.
+    pub fn activate(&self) {
+        if !self.state.active.send_replace(true) {
+            info!("Clipboard watcher activated");
+        }
+    }
+
+    /// Stops capturing. Nothing copied until `resume` is ever reported.
+    pub fn pause(&self) {
+        if !self.state.paused.send_replace(true) {
+            info!("Clipboard watcher paused");
+        }
+    }
+
+    pub fn resume(&self) {
+        if self.state.paused.send_replace(false) {
+            info!("Clipboard watcher resumed");
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.state.paused.borrow()
+    }
+
+    pub fn status(&self) -> WatchStatus {
+        let mode = self
+            .state
+            .mode
+            .lock()
+            .map(|mode| *mode)
+            .unwrap_or(WatchMode::Starting);
+
+        WatchStatus {
+            paused: self.is_paused(),
+            mode,
+            poll_interval_ms: (mode == WatchMode::Polling)
+                .then(|| self.state.interval_ms.load(Ordering::Relaxed)),
+            sequence: self.state.sequence.load(Ordering::Relaxed),
+        }
+    }
+
+    fn set_mode(&self, mode: WatchMode) {
+        if let Ok(mut current) = self.state.mode.lock() {
+            *current = mode;
+        }
+    }
+
+    fn set_interval(&self, interval: Duration) {
+        self.state
+            .interval_ms
+            .store(interval.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn next_sequence(&self) -> u64 {
+        self.state.sequence.fetch_add(1, Ordering::Relaxed) + 1
+    }
+}
+
 pub struct ClipboardWatcher {
     clipboard: Arc<ClipboardManager>,
-    last_content: Arc<RwLock<Option<WatchedContent>>>,
+    last_content: Arc<RwLock<HashMap<Selection, WatchedContent>>>,
+    selections: Vec<Selection>,
     poll_interval: Duration,
-    event_sender: mpsc::Sender<ClipboardEvent>,
+    idle_interval: Duration,
+    events: EventBus,
+    filter: WatchFilter,
+    plugins: PluginHost,
+    control: WatchControl,
 }
 
 impl ClipboardWatcher {
+    /// Publishes every change on `events`; subscribe before starting.
     pub fn new(
         clipboard: Arc<ClipboardManager>,
         poll_interval: Duration,
-    ) -> (Self, mpsc::Receiver<ClipboardEvent>) {
-        let (tx, rx) = mpsc::channel(100);
-
-        let watcher = Self {
+        events: EventBus,
+    ) -> Self {
+        Self {
             clipboard,
-            last_content: Arc::new(RwLock::new(None)),
+            last_content: Arc::new(RwLock::new(HashMap::new())),
+            selections: vec![Selection::Clipboard],
             poll_interval,
-            event_sender: tx,
-        };
+            idle_interval: poll_interval,
+            events,
+            filter: WatchFilter::default(),
+            plugins: PluginHost::default(),
+            control: WatchControl::new(),
+        }
+    }
+
+    /// Lets polls back off towards `idle_interval` while nothing changes,
+    /// returning to the poll interval after the next change.
+    pub fn with_idle_interval(mut self, idle_interval: Duration) -> Self {
+        self.idle_interval = idle_interval.max(self.poll_interval);
+        self
+    }
 
-        (watcher, rx)
+    /// Replaces the default filter, which only skips content marked secret.
+    pub fn with_filter(mut self, filter: WatchFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Runs each copy through the watch plugins before it is published.
+    pub fn with_plugins(mut self, plugins: PluginHost) -> Self {
+        self.plugins = plugins;
+        self
+    }
+
+    /// Also watches PRIMARY (or SECONDARY). Selections the backend does not
+    /// have are skipped.
+    pub fn watch_selection(mut self, selection: Selection) -> Self {
+        if !self.selections.contains(&selection) {
+            self.selections.push(selection);
+        }
+        self
+    }
+
+    /// Leaves the clipboard alone until `WatchControl::activate` is called,
+    /// e.g. once an MCP client subscribes. What is on the clipboard then is
+    /// taken as seen rather than reported.
+    pub fn on_demand(self) -> Self {
+        self.control.state.active.send_replace(false);
+        self
+    }
+
+    /// A handle to pause and resume the watcher once it runs.
+    pub fn control(&self) -> WatchControl {
+        self.control.clone()
     }
 
     pub async fn start_watching(self) {
-        let mut interval_timer = interval(self.poll_interval);
-        interval_timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let selections: Vec<Selection> = self
+            .selections
+            .iter()
+            .copied()
+            .filter(|&selection| self.clipboard.supports_selection(selection))
+            .collect();
+        let mut paused = self.control.state.paused.subscribe();
+
+        let mut active = self.control.state.active.subscribe();
+        if !*active.borrow_and_update() {
+            self.control.set_mode(WatchMode::Idle);
+            info!("Clipboard watcher waiting for a subscriber");
+            let _ = active.wait_for(|active| *active).await;
+            self.rebaseline().await;
+        } else if !self.control.is_paused() {
+            self.check_all().await;
+        }
 
+        // Where the backend can tell us about new owners we only read the
+        // clipboard then; polling is the fallback
+        if let Some(mut changes) = self.clipboard.owner_changes(&selections) {
+            self.control.set_mode(WatchMode::Notifications);
+            info!("Clipboard watcher started (selection owner notifications)");
+
+            loop {
+                tokio::select! {
+                    change = changes.recv() => {
+                        let Some(change) = change else { break };
+                        if !self.control.is_paused() {
+                            self.check_selection(change.selection, change.mime_types).await;
+                        }
+                    }
+                    Ok(()) = paused.changed() => {
+                        if !*paused.borrow_and_update() {
+                            self.rebaseline().await;
+                        }
+                    }
+                }
+            }
+
+            warn!("Selection owner notifications stopped, falling back to polling");
+        }
+
+        self.control.set_mode(WatchMode::Polling);
         info!(
-            "Clipboard watcher started (poll interval: {:?})",
-            self.poll_interval
+            "Clipboard watcher started (poll interval: {:?}, idle: {:?})",
+            self.poll_interval, self.idle_interval
         );
 
+        let mut interval = self.poll_interval;
         loop {
-            interval_timer.tick().await;
+            self.control.set_interval(interval);
+            sleep(interval).await;
+
+            // Copies made while paused are never reported
+            if paused.has_changed().unwrap_or(false) || self.control.is_paused() {
+                let _ = paused.wait_for(|paused| !paused).await;
+                self.rebaseline().await;
+                interval = self.poll_interval;
+                continue;
+            }
 
-            if let Err(e) = self.check_clipboard().await {
-                error!("Clipboard check error: {}", e);
+            interval = if self.check_all().await {
+                self.poll_interval
+            } else {
+                interval.mul_f64(IDLE_BACKOFF).min(self.idle_interval)
+            };
+        }
+    }
+
+    /// Checks every selection; true if any of them changed.
+    async fn check_all(&self) -> bool {
+        let mut changed = false;
+        for &selection in &self.selections {
+            changed |= self.check_selection(selection, None).await;
+        }
+        changed
+    }
+
+    async fn check_selection(&self, selection: Selection, announced: Option<Vec<String>>) -> bool {
+        match self.check_clipboard(selection, announced).await {
+            Ok(changed) => changed,
+            Err(e) => {
+                error!("Clipboard check error ({}): {}", selection, e);
                 // Continue watching despite errors
+                false
             }
         }
     }
 
-    async fn check_clipboard(&self) -> Result<()> {
-        // Get current clipboard content
-        let current_data = match self.clipboard.get_content() {
-            Ok(data) => data,
+    /// Takes whatever is on the clipboard now as seen, without reporting it.
+    async fn rebaseline(&self) {
+        let mut last = self.last_content.write().await;
+        for &selection in &self.selections {
+            if let Ok(fingerprint) = self.clipboard.fingerprint(selection) {
+                Self::withhold(&mut last, selection, fingerprint);
+            }
+        }
+    }
+
+    /// Reports the selection if it changed; true for any change, even one
+    /// that is not reported. `announced` are the MIME types the new owner
+    /// announced, when the backend said.
+    async fn check_clipboard(
+        &self,
+        selection: Selection,
+        announced: Option<Vec<String>>,
+    ) -> Result<bool> {
+        if !self.clipboard.supports_selection(selection) {
+            return Ok(false);
+        }
+
+        // One read: unchanged contents are recognised before anything is
+        // encoded, and the fingerprint kept is that of what gets published
+        let seen = self
+            .last_content
+            .read()
+            .await
+            .get(&selection)
+            .map(|prev| prev.fingerprint);
+        let captured = match seen {
+            Some(seen) => self.clipboard.capture_if_changed(selection, seen),
+            None => self.clipboard.capture(selection).map(Some),
+        };
+        let snapshot = match captured {
+            Ok(Some(snapshot)) => snapshot,
+            Ok(None) => return Ok(false),
             Err(e) => {
                 debug!("No clipboard content or error: {}", e);
-                return Ok(());
+                return Ok(false);
             }
         };
+        let fingerprint = snapshot.fingerprint();
 
-        // Calculate content hash
-        let content_hash = self.calculate_content_hash(&current_data.content);
-        let content_type = self.get_content_type(&current_data.content);
-
-        // Check if content changed
         let mut last = self.last_content.write().await;
 
-        let changed = match &*last {
-            Some(prev) => prev.content_hash != content_hash,
-            None => true,
+        if last
+            .get(&selection)
+            .is_some_and(|prev| prev.fingerprint == fingerprint)
+        {
+            return Ok(false);
+        }
+
+        // The processor's own writes (the path it puts back) are not copies
+        if self.clipboard.origin_of(selection, fingerprint) == Origin::Daemon {
+            debug!("Ignoring our own write to the {} selection", selection);
+            match last.get_mut(&selection) {
+                Some(prev) => {
+                    prev.fingerprint = fingerprint;
+                    prev.timestamp = SystemTime::now();
+                }
+                // Nothing seen before it, e.g. the clipboard started empty
+                None => Self::withhold(&mut last, selection, fingerprint),
+            }
+            return Ok(true);
+        }
+
+        // Password managers mark secrets with a hint target. The content
+        // was read to fingerprint it, but goes no further: it is never
+        // staged, published or logged
+        let mime_types = match announced {
+            Some(mime_types) => mime_types,
+            None => match self.clipboard.formats(selection) {
+                Ok(mime_types) => mime_types,
+                // No telling whether this is a secret, so treat it as one
+                Err(e) if self.filter.honors_hints() => {
+                    debug!("Withholding {} content, no target list: {}", selection, e);
+                    Self::withhold(&mut last, selection, fingerprint);
+                    return Ok(true);
+                }
+                Err(_) => Vec::new(),
+            },
         };
+        if self.filter.is_sensitive(&mime_types) {
+            debug!("Skipping {} content marked as sensitive", selection);
+            Self::withhold(&mut last, selection, fingerprint);
+            return Ok(true);
+        }
 
-        if !changed {
-            return Ok(());
+        if let Some(reason) = self.filter.rejects(&snapshot) {
+            debug!("Skipping {} content: {}", selection, reason);
+            Self::withhold(&mut last, selection, fingerprint);
+            return Ok(true);
         }
+        let (snapshot, current_data) = if self.plugins.is_empty() {
+            let current_data = self.clipboard.describe(&snapshot)?;
+            (snapshot, current_data)
+        } else {
+            // Plugins may take a while, and nothing else waits on the lock
+            // for them
+            drop(last);
+            let outcome = self.run_plugins(selection, snapshot).await;
+            last = self.last_content.write().await;
+            if last
+                .get(&selection)
+                .is_some_and(|prev| prev.fingerprint == fingerprint)
+            {
+                return Ok(false);
+            }
+
+            // A plugin that fails withholds the copy rather than let through
+            // what it was meant to redact
+            match outcome {
+                Ok(Some(outcome)) => outcome,
+                Ok(None) => {
+                    Self::withhold(&mut last, selection, fingerprint);
+                    return Ok(true);
+                }
+                Err(e) => {
+                    error!("Withholding {} content: {}", selection, e);
+                    Self::withhold(&mut last, selection, fingerprint);
+                    return Ok(true);
+                }
+            }
+        };
+        let content_type = self.get_content_type(&current_data.content);
+        let sequence = self.control.next_sequence();
 
         // Update last content
-        *last = Some(WatchedContent {
-            content_hash: content_hash.clone(),
-            timestamp: SystemTime::now(),
-            content_type: content_type.clone(),
-        });
+        last.insert(
+            selection,
+            WatchedContent {
+                sequence,
+                fingerprint,
+                timestamp: SystemTime::now(),
+                content_type: content_type.clone(),
+            },
+        );
         drop(last); // Release write lock
 
         // Emit event for new content
-        info!("New clipboard content detected: {:?}", content_type);
+        info!("New {} content detected: {:?}", selection, content_type);
 
         let event = ClipboardEvent {
+            sequence,
             content: current_data,
+            snapshot,
+            mime_types,
             staged_path: None,
             symlink_path: None,
         };
 
-        if let Err(e) = self.event_sender.send(event).await {
-            warn!("Failed to send clipboard event: {}", e);
-        }
+        self.events.publish(event).await;
 
-        Ok(())
+        Ok(true)
     }
 
-    fn calculate_content_hash(&self, content: &ClipboardContent) -> String {
-        use sha2::{Digest, Sha256};
-        let mut hasher = Sha256::new();
-
-        match content {
-            ClipboardContent::Text { data, .. } => {
-                hasher.update(b"text:");
-                hasher.update(data.as_bytes());
-            }
-            ClipboardContent::ImagePng {
-                data,
-                file,
-                width,
-                height,
-                size,
-            }
-            | ClipboardContent::ImageJpeg {
-                data,
-                file,
-                width,
-                height,
-                size,
-            } => {
-                hasher.update(b"image:");
-                hasher.update(width.to_le_bytes());
-                hasher.update(height.to_le_bytes());
-                hasher.update(size.to_le_bytes());
-
-                if let Some(data) = data {
-                    hasher.update(data.as_bytes());
-                } else if let Some(file) = file {
-                    hasher.update(file.as_bytes());
-                }
+    /// Runs the watch plugins over the whole of `snapshot`, however large,
+    /// and describes what they leave. None if the copy must not be
+    /// reported.
+    async fn run_plugins(
+        &self,
+        selection: Selection,
+        snapshot: ClipboardSnapshot,
+    ) -> Result<Option<(ClipboardSnapshot, ClipboardData)>> {
+        let data = self.clipboard.describe_in_full(&snapshot)?;
+        let (data, verdict) = self.plugins.apply(data).await?;
+
+        let snapshot = match verdict {
+            Verdict::Pass => snapshot,
+            Verdict::Rewritten => match snapshot.rewritten(&data.content) {
+                Some(snapshot) => snapshot,
+                None => return Ok(None),
+            },
+            Verdict::Veto { plugin, reason } => {
+                debug!("Plugin {} vetoed {} content: {}", plugin, selection, reason);
+                return Ok(None);
             }
-        }
+        };
 
-        format!("{:x}", hasher.finalize())
+        // Published like any other copy, truncated, with the annotations
+        let mut current_data = self.clipboard.describe(&snapshot)?;
+        current_data.metadata.annotations = data.metadata.annotations;
+        Ok(Some((snapshot, current_data)))
+    }
+
+    /// Remembers content that is not reported (filtered, or copied while
+    /// paused), so it is not read again on every poll, without using up a
+    /// sequence number.
+    fn withhold(
+        last: &mut HashMap<Selection, WatchedContent>,
+        selection: Selection,
+        fingerprint: u64,
+    ) {
+        let sequence = last.get(&selection).map_or(0, |prev| prev.sequence);
+        last.insert(
+            selection,
+            WatchedContent {
+                sequence,
+                fingerprint,
+                timestamp: SystemTime::now(),
+                content_type: ContentType::Filtered,
+            },
+        );
     }
 
     fn get_content_type(&self, content: &ClipboardContent) -> ContentType {
         match content {
-            ClipboardContent::Text { data, .. } => ContentType::Text(data.len()),
+            ClipboardContent::Text { data, .. } | ClipboardContent::Markdown { data, .. } => {
+                ContentType::Text(data.len())
+            }
+            ClipboardContent::Html { data, .. } => ContentType::Html(data.len()),
+            ClipboardContent::Files { files } => ContentType::Files(files.len()),
             ClipboardContent::ImagePng { width, height, .. } => {
                 ContentType::Image("png".to_string(), *width, *height)
             }
             ClipboardContent::ImageJpeg { width, height, .. } => {
                 ContentType::Image("jpeg".to_string(), *width, *height)
             }
+            ClipboardContent::Target { mime, size, .. } => ContentType::Target(mime.clone(), *size),
         }
     }
 }
@@ -183,12 +559,15 @@ pub mod platform {
     impl DualClipboard {
         /// Sets both text (file path) and image data in clipboard
         /// Terminal apps will get the text, image apps will get the image
-        pub fn set_dual_content(path: &str, _image_data: &[u8]) -> Result<()> {
+        pub fn set_dual_content(
+            clipboard: &ClipboardManager,
+            path: &str,
+            _image_data: &[u8],
+        ) -> Result<()> {
             // For now, let's use a simpler approach that definitely works
             // We'll just set the text path, and document that dual format
             // requires more complex macOS integration
 
-            let clipboard = ClipboardManager::new()?;
             clipboard.set_content(&ClipboardContent::Text {
                 data: path.to_string(),
                 truncated: None,
@@ -200,19 +579,52 @@ pub mod platform {
     }
 }
 
-#[cfg(not(target_os = "macos"))]
+// X11 and Wayland let one owner offer several targets
+#[cfg(target_os = "linux")]
+pub mod platform {
+    use super::*;
+    use crate::clipboard::backend::{MIME_PNG, MIME_TEXT_UTF8};
+
+    pub struct DualClipboard;
+
+    impl DualClipboard {
+        /// Offers the path as text and the original PNG from one owner:
+        /// terminals paste the path, image apps still paste the picture.
+        /// The manager must outlive the call, as X11 and Wayland drop the
+        /// contents once their owner goes away.
+        pub fn set_dual_content(
+            clipboard: &ClipboardManager,
+            path: &str,
+            image_data: &[u8],
+        ) -> Result<()> {
+            clipboard.set_formats(
+                Selection::Clipboard,
+                &[
+                    (MIME_TEXT_UTF8.to_string(), path.as_bytes().to_vec()),
+                    (MIME_PNG.to_string(), image_data.to_vec()),
+                ],
+            )
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
 pub mod platform {
     use super::*;
 
     pub struct DualClipboard;
 
     impl DualClipboard {
-        pub fn set_dual_content(path: &str, _image_data: &[u8]) -> Result<()> {
+        /// Writes through the caller's manager, which must outlive the call.
+        pub fn set_dual_content(
+            clipboard: &ClipboardManager,
+            path: &str,
+            _image_data: &[u8],
+        ) -> Result<()> {
             // On other platforms, we'll just set the path as text
-            // This is a fallback - could implement X11/Win32 specific code
+            // This is a fallback - could implement Win32 specific code
             warn!("Dual clipboard not fully implemented for this platform");
 
-            let clipboard = ClipboardManager::new()?;
             clipboard.set_content(&ClipboardContent::Text {
                 data: path.to_string(),
                 truncated: None,
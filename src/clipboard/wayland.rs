@@ -0,0 +1,116 @@
+use tokio::sync::mpsc;
+
+use super::backend::{
+    decode_image, encode_png, is_text_mime, unsupported_format, ClipboardBackend, OwnerChange,
+    RawImage, Selection, MIME_PNG, MIME_TEXT_UTF8,
+};
+use super::linux::wayland;
+use crate::{ClaudeUtilsError, Result};
+
+/// The Wayland clipboard through the data-control protocols.
+///
+/// Unlike arboard this never falls back to XWayland, and the compositor
+/// reports every new selection whether or not one of our windows has
+/// focus, so the watcher is told about copies instead of polling. Needs
+/// `ext-data-control` or `wlr-data-control` (wlroots, KDE; not GNOME).
+pub struct WaylandClipboard;
+
+impl WaylandClipboard {
+    pub fn new() -> Result<Self> {
+        if !Self::is_available() {
+            return Err(ClaudeUtilsError::Clipboard(
+                "WAYLAND_DISPLAY is not set".to_string(),
+            ));
+        }
+
+        Ok(Self)
+    }
+
+    pub fn is_available() -> bool {
+        std::env::var_os("WAYLAND_DISPLAY").is_some_and(|v| !v.is_empty())
+    }
+
+    /// The offered target matching `mime`, treating text names as one.
+    fn find_target(&self, selection: Selection, mime: &str) -> Result<String> {
+        wayland::targets(selection)?
+            .into_iter()
+            .find(|offered| offered == mime || (is_text_mime(offered) && is_text_mime(mime)))
+            .ok_or_else(|| unsupported_format(self.name(), mime))
+    }
+}
+
+impl ClipboardBackend for WaylandClipboard {
+    fn name(&self) -> &'static str {
+        "wayland"
+    }
+
+    fn supports_selection(&self, selection: Selection) -> bool {
+        selection != Selection::Secondary
+    }
+
+    fn get_text(&mut self, selection: Selection) -> Result<String> {
+        let bytes = self.get_format(selection, MIME_TEXT_UTF8)?;
+
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    fn set_text(&mut self, selection: Selection, text: &str) -> Result<()> {
+        self.set_formats(
+            selection,
+            &[(MIME_TEXT_UTF8.to_string(), text.as_bytes().to_vec())],
+        )
+    }
+
+    fn get_image(&mut self, selection: Selection) -> Result<RawImage> {
+        let targets = wayland::targets(selection)?;
+        let mime = targets
+            .iter()
+            .find(|mime| *mime == MIME_PNG)
+            .or_else(|| targets.iter().find(|mime| mime.starts_with("image/")))
+            .ok_or_else(|| ClaudeUtilsError::Clipboard("No image in clipboard".to_string()))?;
+
+        decode_image(&wayland::read(selection, mime)?)
+    }
+
+    fn set_image(&mut self, selection: Selection, image: &RawImage) -> Result<()> {
+        self.set_formats(selection, &[(MIME_PNG.to_string(), encode_png(image)?)])
+    }
+
+    fn formats(&mut self, selection: Selection) -> Result<Vec<String>> {
+        wayland::targets(selection)
+    }
+
+    fn get_format(&mut self, selection: Selection, mime: &str) -> Result<Vec<u8>> {
+        let target = self.find_target(selection, mime)?;
+
+        wayland::read(selection, &target)
+    }
+
+    fn format_size(&mut self, selection: Selection, mime: &str) -> Option<usize> {
+        wayland::size(selection, mime).ok()
+    }
+
+    fn set_formats(&mut self, selection: Selection, formats: &[(String, Vec<u8>)]) -> Result<()> {
+        if formats.is_empty() {
+            return wayland::clear(selection);
+        }
+
+        // Served from a background thread until someone else copies
+        wayland::offer(selection, formats, false)
+    }
+
+    fn owner_changes(
+        &mut self,
+        selections: &[Selection],
+    ) -> Option<mpsc::UnboundedReceiver<OwnerChange>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        match wayland::watch_owners(selections, tx) {
+            Ok(()) => Some(rx),
+            Err(e) => {
+                tracing::warn!("Wayland selection notifications unavailable: {}", e);
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    clipboard::{
+        delivery::{Delivery, LinkTemplate},
+        filter::FilterConfig,
+        hooks::{HookConfig, DEFAULT_MAX_CONCURRENT_HOOKS},
+        rules::RuleConfig,
+    },
+    plugins::PluginConfig,
+    ClaudeUtilsError, Result,
+};
+
+/// Settings read from `~/.claude-utils/config.toml`. Every table is
+/// optional; a missing file means the defaults.
+///
+/// ```toml
+/// [watch]
+/// poll_interval_ms = 250
+/// idle_poll_interval_ms = 2000
+/// delivery = "hardlink"
+/// link_template = "{slug}-{hash}"
+///
+/// [watch.filter]
+/// ignore_types = ["image/*"]
+/// max_size = 10485760
+/// ignore_patterns = ["^sk-[A-Za-z0-9]{20,}$"]
+///
+/// [[watch.rules]]
+/// match = { types = ["image/*"] }
+/// actions = ["stage", "symlink", "rewrite", "notify"]
+///
+/// [[watch.hooks]]
+/// command = ["oxipng", "--strip", "safe"]
+/// rules = ["images"]
+///
+/// [[plugins]]
+/// name = "redact"
+/// path = "/home/me/.claude-utils/plugins/redact.wasm"
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub watch: WatchConfig,
+    pub plugins: Vec<PluginConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchConfig {
+    /// How often to poll right after a change, where the backend cannot
+    /// report new owners
+    pub poll_interval_ms: u64,
+    /// How often to poll once the clipboard has been idle for a while
+    pub idle_poll_interval_ms: u64,
+    /// How staged files are put in the symlink directory
+    pub delivery: Delivery,
+    /// Their names, e.g. `"{slug}-{hash}"`
+    pub link_template: LinkTemplate,
+    pub filter: FilterConfig,
+    /// Replaces the built-in processing rules when given
+    pub rules: Option<Vec<RuleConfig>>,
+    pub hooks: Vec<HookConfig>,
+    /// How many background hook commands may run at once
+    pub max_concurrent_hooks: usize,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_ms: 250,
+            idle_poll_interval_ms: 2000,
+            delivery: Delivery::default(),
+            link_template: LinkTemplate::default(),
+            filter: FilterConfig::default(),
+            rules: None,
+            hooks: Vec::new(),
+            max_concurrent_hooks: DEFAULT_MAX_CONCURRENT_HOOKS,
+        }
+    }
+}
+
+impl Config {
+    pub fn default_path() -> PathBuf {
+        crate::state_dir().join("config.toml")
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+
+        toml::from_str(&text)
+            .map_err(|e| ClaudeUtilsError::Config(format!("{}: {e}", path.display())))
+    }
+
+    /// Loads `path`, or the default location when `None`. Only an
+    /// explicitly named file has to exist.
+    pub fn load_or_default(path: Option<&Path>) -> Result<Self> {
+        match path {
+            Some(path) => Self::load(path),
+            None => {
+                let path = Self::default_path();
+                if path.exists() {
+                    Self::load(&path)
+                } else {
+                    Ok(Self::default())
+                }
+            }
+        }
+    }
+}
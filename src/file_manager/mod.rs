@@ -1,5 +1,5 @@
 use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
@@ -11,6 +11,8 @@ use crate::Result;
 #[derive(Debug, Clone)]
 pub struct StagedFile {
     pub path: PathBuf,
+    /// SHA-256 of the content, in hex
+    pub hash: String,
     pub size: usize,
     pub format: String,
     pub created_at: SystemTime,
@@ -39,6 +41,8 @@ impl Default for FileManagerConfig {
 pub struct FileManager {
     config: FileManagerConfig,
     cache: Arc<Mutex<HashMap<String, StagedFile>>>,
+    /// Directories whose symlinks keep the staged files they point at
+    link_dirs: Arc<Mutex<Vec<PathBuf>>>,
 }
 
 impl FileManager {
@@ -49,6 +53,7 @@ impl FileManager {
         let manager = Self {
             config,
             cache: Arc::new(Mutex::new(HashMap::new())),
+            link_dirs: Arc::new(Mutex::new(Vec::new())),
         };
 
         // Start cleanup task
@@ -84,6 +89,7 @@ impl FileManager {
 
         let staged_file = StagedFile {
             path: file_path,
+            hash: hash.clone(),
             size: data.len(),
             format: format.to_string(),
             created_at: SystemTime::now(),
@@ -114,6 +120,7 @@ impl FileManager {
 
         let staged_file = StagedFile {
             path: file_path,
+            hash: hash.clone(),
             size: data.len(),
             format: "txt".to_string(),
             created_at: SystemTime::now(),
@@ -125,6 +132,35 @@ impl FileManager {
         Ok(staged_file)
     }
 
+    /// Stages arbitrary bytes under the given extension, without a thumbnail.
+    pub async fn stage_data(&self, data: &[u8], extension: &str) -> Result<StagedFile> {
+        let hash = self.calculate_hash(data);
+        let filename = format!("clip-{}.{}", &hash[..8], extension);
+        let file_path = self.config.staging_dir.join(&filename);
+
+        // Check cache
+        if let Some(staged) = self.get_from_cache(&hash) {
+            if file_path.exists() {
+                return Ok(staged);
+            }
+        }
+
+        fs::write(&file_path, data).await?;
+
+        let staged_file = StagedFile {
+            path: file_path,
+            hash: hash.clone(),
+            size: data.len(),
+            format: extension.to_string(),
+            created_at: SystemTime::now(),
+            thumbnail_path: None,
+        };
+
+        self.update_cache(hash, staged_file.clone());
+
+        Ok(staged_file)
+    }
+
     async fn generate_thumbnail(
         &self,
         file_path: &Path,
@@ -180,8 +216,20 @@ impl FileManager {
         }
     }
 
+    /// Keeps staged files past their age limit for as long as a symlink in
+    /// `dir` points at them. The links are looked for on every cleanup, so
+    /// ones made before a restart count too.
+    pub fn keep_linked_from(&self, dir: PathBuf) {
+        if let Ok(mut link_dirs) = self.link_dirs.lock() {
+            if !link_dirs.contains(&dir) {
+                link_dirs.push(dir);
+            }
+        }
+    }
+
     fn start_cleanup_task(&self) {
         let cache = self.cache.clone();
+        let link_dirs = self.link_dirs.clone();
         let staging_dir = self.config.staging_dir.clone();
         let max_age = self.config.max_file_age;
         let interval = self.config.cleanup_interval;
@@ -193,6 +241,7 @@ impl FileManager {
                 interval_timer.tick().await;
 
                 info!("Running cleanup task");
+                let linked = linked_files(&link_dirs, &staging_dir).await;
 
                 // Clean up old files
                 match fs::read_dir(&staging_dir).await {
@@ -201,8 +250,8 @@ impl FileManager {
                             if let Ok(metadata) = entry.metadata().await {
                                 if let Ok(modified) = metadata.modified() {
                                     if let Ok(age) = modified.elapsed() {
-                                        if age > max_age {
-                                            let path = entry.path();
+                                        let path = entry.path();
+                                        if age > max_age && !linked.contains(&path) {
                                             match fs::remove_file(&path).await {
                                                 Ok(_) => {
                                                     info!("Cleaned up old file: {}", path.display())
@@ -236,3 +285,31 @@ impl FileManager {
         &self.config.staging_dir
     }
 }
+
+/// The staged files that symlinks in `link_dirs` point at.
+async fn linked_files(link_dirs: &Mutex<Vec<PathBuf>>, staging_dir: &Path) -> HashSet<PathBuf> {
+    let dirs = link_dirs
+        .lock()
+        .map(|dirs| dirs.clone())
+        .unwrap_or_default();
+    let mut linked = HashSet::new();
+
+    for dir in dirs {
+        let mut entries = match fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Failed to read link directory {}: {}", dir.display(), e);
+                continue;
+            }
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if let Ok(target) = fs::read_link(entry.path()).await {
+                if target.starts_with(staging_dir) {
+                    linked.insert(target);
+                }
+            }
+        }
+    }
+
+    linked
+}
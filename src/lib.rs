@@ -1,6 +1,8 @@
 pub mod clipboard;
+pub mod config;
 pub mod file_manager;
 pub mod mcp;
+pub mod plugins;
 
 use thiserror::Error;
 
@@ -26,6 +28,12 @@ pub enum ClaudeUtilsError {
 
     #[error("Server error: {0}")]
     Server(String),
+
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    #[error("Plugin error: {0}")]
+    Plugin(String),
 }
 
 pub type Result<T> = std::result::Result<T, ClaudeUtilsError>;
@@ -33,8 +41,16 @@ pub type Result<T> = std::result::Result<T, ClaudeUtilsError>;
 pub const DEFAULT_PORT: u16 = 3830;
 pub const DEFAULT_HOST: &str = "127.0.0.1";
 pub const STAGING_DIR_NAME: &str = "claude-utils";
+pub const STATE_DIR_NAME: &str = ".claude-utils";
 pub const MAX_INLINE_SIZE: usize = 65536; // 64KB
 pub const CLEANUP_INTERVAL_MINS: u64 = 15;
 
+/// Per-user directory for the auth token and other persistent state.
+pub fn state_dir() -> std::path::PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| std::path::PathBuf::from("."))
+        .join(STATE_DIR_NAME)
+}
+
 #[cfg(test)]
 mod main_test;
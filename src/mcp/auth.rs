@@ -14,10 +14,7 @@ pub struct AuthConfig {
 
 impl Default for AuthConfig {
     fn default() -> Self {
-        let token_path = dirs::home_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join(".claude-utils")
-            .join("auth.token");
+        let token_path = crate::state_dir().join("auth.token");
 
         Self {
             token_path,
@@ -0,0 +1,146 @@
+//! A minimal client for a running daemon, so one-shot CLI writes can leave
+//! the selection with a process that outlives them.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use reqwest::header::AUTHORIZATION;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::{
+    clipboard::{ClipboardContent, Selection},
+    mcp::auth::AuthConfig,
+    ClaudeUtilsError, Result,
+};
+
+/// How long to wait for `/health` before assuming no daemon is running.
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+const CALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct DaemonClient {
+    http: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+    backend: String,
+}
+
+impl DaemonClient {
+    /// Connects to the daemon on `host:port`, if one answers.
+    pub async fn connect(host: &str, port: u16) -> Option<Self> {
+        let http = reqwest::Client::new();
+        let base_url = format!("http://{host}:{port}");
+
+        let health: Value = http
+            .get(format!("{base_url}/health"))
+            .timeout(PROBE_TIMEOUT)
+            .send()
+            .await
+            .ok()?
+            .json()
+            .await
+            .ok()?;
+
+        if health["status"] != "healthy" {
+            return None;
+        }
+
+        let token = std::fs::read_to_string(AuthConfig::default().token_path)
+            .ok()
+            .map(|token| token.trim().to_string());
+
+        Some(Self {
+            http,
+            base_url,
+            token,
+            backend: health["backend"].as_str().unwrap_or("unknown").to_string(),
+        })
+    }
+
+    /// The clipboard backend the daemon runs on.
+    pub fn backend(&self) -> &str {
+        &self.backend
+    }
+
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value> {
+        let mut request = self
+            .http
+            .post(format!("{}/rpc", self.base_url))
+            .timeout(CALL_TIMEOUT)
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tools/call",
+                "params": {"name": name, "arguments": arguments},
+            }));
+
+        if let Some(token) = &self.token {
+            request = request.header(AUTHORIZATION, format!("Bearer {token}"));
+        }
+
+        let response: Value = request
+            .send()
+            .await
+            .map_err(|e| ClaudeUtilsError::Server(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| ClaudeUtilsError::Server(e.to_string()))?;
+
+        match response.get("error") {
+            Some(error) => Err(ClaudeUtilsError::McpProtocol(
+                error["message"]
+                    .as_str()
+                    .unwrap_or("Unknown error")
+                    .to_string(),
+            )),
+            None => Ok(response["result"].clone()),
+        }
+    }
+
+    /// Has the daemon write `content`, so it keeps owning the selection.
+    pub async fn set_content(
+        &self,
+        selection: Selection,
+        content: &ClipboardContent,
+    ) -> Result<()> {
+        let mut arguments = set_arguments(content)?;
+        arguments["selection"] = json!(selection);
+
+        self.call_tool("clipboard.set", arguments).await.map(|_| ())
+    }
+}
+
+/// `clipboard.set` arguments for `content`. Files are sent as their data,
+/// since the daemon reads no files for HTTP clients.
+fn set_arguments(content: &ClipboardContent) -> Result<Value> {
+    match content {
+        ClipboardContent::Text { data, .. } | ClipboardContent::Markdown { data, .. } => {
+            Ok(json!({"type": "text/plain", "data": data}))
+        }
+        ClipboardContent::Html { data, .. } => Ok(json!({"type": "text/html", "data": data})),
+        ClipboardContent::ImagePng { data, file, .. } => {
+            Ok(json!({"type": "image/png", "data": inline(data, file)?}))
+        }
+        ClipboardContent::ImageJpeg { data, file, .. } => {
+            Ok(json!({"type": "image/jpeg", "data": inline(data, file)?}))
+        }
+        ClipboardContent::Target {
+            mime,
+            data,
+            encoding,
+            file,
+            ..
+        } if mime.starts_with("image/") && encoding.as_deref() != Some("utf-8") => {
+            Ok(json!({"type": mime, "data": inline(data, file)?}))
+        }
+        _ => Err(ClaudeUtilsError::Clipboard(
+            "clipboard.set cannot carry this content".to_string(),
+        )),
+    }
+}
+
+/// Base64 `data`, or the contents of `file` encoded the same way.
+fn inline(data: &Option<String>, file: &Option<String>) -> Result<Option<String>> {
+    match (data, file) {
+        (None, Some(file)) => Ok(Some(BASE64.encode(std::fs::read(file)?))),
+        _ => Ok(data.clone()),
+    }
+}
@@ -1,6 +1,10 @@
 pub mod auth;
+pub mod client;
 pub mod protocol;
+mod resources;
 pub mod server;
+pub mod session;
+pub mod stdio;
 
 pub use protocol::*;
 pub use server::McpServer;
@@ -39,14 +39,17 @@ pub struct JsonRpcNotification {
 
 // MCP Protocol types
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct InitializeRequest {
     pub protocol_version: String,
+    #[serde(default)]
     pub capabilities: ClientCapabilities,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub client_info: Option<ClientInfo>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct InitializeResponse {
     pub protocol_version: String,
     pub capabilities: ServerCapabilities,
@@ -54,7 +57,7 @@ pub struct InitializeResponse {
     pub server_info: Option<ServerInfo>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ClientCapabilities {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub experimental: Option<Value>,
@@ -73,8 +76,14 @@ pub struct ServerCapabilities {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolsCapability {}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ResourcesCapability {}
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourcesCapability {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subscribe: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub list_changed: Option<bool>,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PromptsCapability {}
@@ -95,6 +104,7 @@ pub struct ServerInfo {
 
 // Tool definitions
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Tool {
     pub name: String,
     pub description: String,
@@ -123,13 +133,52 @@ pub struct ToolCallResponse {
 pub enum Content {
     #[serde(rename = "text")]
     Text { text: String },
-    #[serde(rename = "image")]
+    #[serde(rename = "image", rename_all = "camelCase")]
     Image {
         data: String, // base64
         mime_type: String,
     },
 }
 
+// Resource definitions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Resource {
+    pub uri: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceListResponse {
+    pub resources: Vec<Resource>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ResourceContents {
+    #[serde(rename_all = "camelCase")]
+    Text {
+        uri: String,
+        mime_type: String,
+        text: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Blob {
+        uri: String,
+        mime_type: String,
+        blob: String, // base64
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceReadResponse {
+    pub contents: Vec<ResourceContents>,
+}
+
 // Error codes
 pub const PARSE_ERROR: i32 = -32700;
 pub const INVALID_REQUEST: i32 = -32600;
@@ -137,11 +186,28 @@ pub const METHOD_NOT_FOUND: i32 = -32601;
 pub const INVALID_PARAMS: i32 = -32602;
 pub const INTERNAL_ERROR: i32 = -32603;
 
+// MCP protocol revisions
+pub const PROTOCOL_VERSION: &str = "2025-03-26";
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-03-26", "2024-11-05"];
+
 // MCP methods
 pub const INITIALIZE: &str = "initialize";
 pub const INITIALIZED: &str = "initialized";
+pub const NOTIFICATIONS_INITIALIZED: &str = "notifications/initialized";
+pub const PING: &str = "ping";
 pub const TOOLS_LIST: &str = "tools/list";
 pub const TOOLS_CALL: &str = "tools/call";
+pub const RESOURCES_LIST: &str = "resources/list";
+pub const RESOURCES_READ: &str = "resources/read";
+pub const RESOURCES_SUBSCRIBE: &str = "resources/subscribe";
+pub const RESOURCES_UNSUBSCRIBE: &str = "resources/unsubscribe";
+
+// Server-initiated notifications
+pub const NOTIFICATIONS_RESOURCES_UPDATED: &str = "notifications/resources/updated";
+pub const NOTIFICATIONS_CLIPBOARD_CHANGED: &str = "notifications/clipboard/changed";
+
+/// The clipboard selection as an MCP resource.
+pub const CLIPBOARD_RESOURCE_URI: &str = "clipboard://current";
 
 // Helper functions
 pub fn create_error_response(id: Option<Value>, code: i32, message: String) -> JsonRpcResponse {
@@ -165,3 +231,11 @@ pub fn create_success_response(id: Option<Value>, result: Value) -> JsonRpcRespo
         error: None,
     }
 }
+
+pub fn create_notification(method: &str, params: Value) -> JsonRpcNotification {
+    JsonRpcNotification {
+        jsonrpc: "2.0".to_string(),
+        method: method.to_string(),
+        params: Some(params),
+    }
+}
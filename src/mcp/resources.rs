@@ -0,0 +1,198 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::{
+    clipboard::{backend::is_text_mime, events::LagPolicy, watcher::ClipboardEvent, Selection},
+    mcp::{protocol::*, server::McpServerState, session::Session},
+};
+
+#[derive(Deserialize)]
+struct ResourceParams {
+    uri: String,
+}
+
+fn parse_uri(params: Option<Value>) -> std::result::Result<String, String> {
+    let params = params
+        .and_then(|p| serde_json::from_value::<ResourceParams>(p).ok())
+        .ok_or_else(|| "Missing resource uri".to_string())?;
+
+    if params.uri != CLIPBOARD_RESOURCE_URI {
+        return Err(format!("Unknown resource: {}", params.uri));
+    }
+
+    Ok(params.uri)
+}
+
+pub(crate) async fn handle_resources_list(request: JsonRpcRequest) -> JsonRpcResponse {
+    let response = ResourceListResponse {
+        resources: vec![Resource {
+            uri: CLIPBOARD_RESOURCE_URI.to_string(),
+            name: "Clipboard".to_string(),
+            description: Some(
+                "The current clipboard, one entry per format it holds. Subscribe to hear \
+                 about every copy."
+                    .to_string(),
+            ),
+            mime_type: None,
+        }],
+    };
+
+    create_success_response(request.id, serde_json::to_value(response).unwrap())
+}
+
+pub(crate) async fn handle_resources_read(
+    state: McpServerState,
+    request: JsonRpcRequest,
+) -> JsonRpcResponse {
+    let uri = match parse_uri(request.params.clone()) {
+        Ok(uri) => uri,
+        Err(message) => return create_error_response(request.id, INVALID_PARAMS, message),
+    };
+
+    let snapshot = match state.clipboard.capture(Selection::Clipboard) {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            return create_error_response(
+                request.id,
+                INTERNAL_ERROR,
+                format!("Clipboard error: {e}"),
+            )
+        }
+    };
+
+    let contents = snapshot
+        .formats()
+        .iter()
+        .map(|(mime, bytes)| {
+            if mime.starts_with("text/") || is_text_mime(mime) {
+                ResourceContents::Text {
+                    uri: uri.clone(),
+                    mime_type: mime.clone(),
+                    text: String::from_utf8_lossy(bytes).into_owned(),
+                }
+            } else {
+                ResourceContents::Blob {
+                    uri: uri.clone(),
+                    mime_type: mime.clone(),
+                    blob: BASE64.encode(bytes),
+                }
+            }
+        })
+        .collect();
+
+    let response = ResourceReadResponse { contents };
+    create_success_response(request.id, serde_json::to_value(response).unwrap())
+}
+
+pub(crate) async fn handle_resources_subscribe(
+    state: McpServerState,
+    session: Option<Arc<Session>>,
+    request: JsonRpcRequest,
+    subscribe: bool,
+) -> JsonRpcResponse {
+    let uri = match parse_uri(request.params.clone()) {
+        Ok(uri) => uri,
+        Err(message) => return create_error_response(request.id, INVALID_PARAMS, message),
+    };
+
+    // The plain JSON-RPC endpoint has nowhere to push notifications
+    let Some(session) = session else {
+        return create_error_response(
+            request.id,
+            INVALID_REQUEST,
+            "Subscriptions need a session; use /mcp or stdio".to_string(),
+        );
+    };
+
+    if subscribe {
+        session.subscribe_resource(&uri);
+        if let Some(control) = &state.watch {
+            control.activate();
+        }
+    } else {
+        session.unsubscribe_resource(&uri);
+    }
+
+    create_success_response(request.id, json!({}))
+}
+
+/// Forwards the watcher's events to every MCP session: `resources/updated`
+/// for sessions subscribed to the clipboard, and `clipboard/changed` with
+/// the details for all of them. Also records each event's sequence number,
+/// which `clipboard.wait` waits on.
+pub(crate) fn spawn_notifier(state: McpServerState) {
+    // Sessions only care about the latest copies; never hold up the watcher
+    let mut events = state.events.subscribe("mcp", 16, LagPolicy::DropOldest);
+
+    tokio::spawn(async move {
+        while let Some(event) = events.recv().await {
+            let selection = event.content.metadata.selection;
+            state.sequences.send_modify(|sequences| {
+                sequences.insert(selection, event.sequence);
+            });
+
+            let sessions = state.sessions.all().await;
+            if sessions.is_empty() {
+                continue;
+            }
+
+            let changed = clipboard_changed(&event);
+            let updated = to_message(create_notification(
+                NOTIFICATIONS_RESOURCES_UPDATED,
+                json!({"uri": CLIPBOARD_RESOURCE_URI}),
+            ));
+
+            debug!(
+                "Notifying {} MCP sessions of a {} change",
+                sessions.len(),
+                selection
+            );
+            for session in sessions {
+                if selection.is_clipboard() && session.is_subscribed(CLIPBOARD_RESOURCE_URI) {
+                    session.send(updated.clone());
+                }
+                session.send(changed.clone());
+            }
+        }
+    });
+}
+
+fn clipboard_changed(event: &ClipboardEvent) -> Value {
+    let content_type = serde_json::to_value(&event.content.content)
+        .ok()
+        .and_then(|content| content.get("type").cloned())
+        .unwrap_or(Value::Null);
+    let size = event
+        .snapshot
+        .formats()
+        .first()
+        .map(|(_, bytes)| bytes.len())
+        .unwrap_or(0);
+
+    // Only watch mode stages copies; clipboard.get stages on demand
+    let staged_path = event
+        .staged_path
+        .as_ref()
+        .map(|path| path.to_string_lossy().to_string());
+
+    to_message(create_notification(
+        NOTIFICATIONS_CLIPBOARD_CHANGED,
+        json!({
+            "uri": CLIPBOARD_RESOURCE_URI,
+            "selection": event.content.metadata.selection,
+            "sequence": event.sequence,
+            "type": content_type,
+            "size": size,
+            "mime_types": event.mime_types,
+            "staged_path": staged_path,
+            "timestamp": event.content.metadata.timestamp,
+        }),
+    ))
+}
+
+fn to_message(notification: JsonRpcNotification) -> Value {
+    serde_json::to_value(notification).unwrap()
+}
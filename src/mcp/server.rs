@@ -1,23 +1,36 @@
 use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::{
     extract::{Query, State},
-    http::{header, HeaderMap, StatusCode},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
 use serde::Deserialize;
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::RwLock;
-use tower_http::cors::CorsLayer;
-use tracing::{error, info};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::watch;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tracing::{error, info, warn};
 
 use crate::{
-    clipboard::{ClipboardContent, ClipboardManager},
+    clipboard::{
+        events::{EventBus, LagPolicy},
+        html::html_to_markdown,
+        watcher::WatchControl,
+        ClipboardContent, ClipboardManager, FileEntry, Selection,
+    },
     file_manager::FileManager,
-    mcp::{auth::AuthManager, protocol::*},
+    mcp::{
+        auth::AuthManager,
+        protocol::*,
+        resources,
+        session::{Session, SessionManager, SESSION_HEADER},
+    },
+    plugins::{PluginHost, PluginInput, PluginStage},
     ClaudeUtilsError, Result,
 };
 
@@ -26,9 +39,36 @@ pub struct McpServerState {
     pub clipboard: Arc<ClipboardManager>,
     pub file_manager: Arc<FileManager>,
     pub auth_manager: Arc<AuthManager>,
-    pub initialized: Arc<RwLock<bool>>,
+    pub sessions: Arc<SessionManager>,
+    pub events: EventBus,
+    /// The sequence number of the latest watcher event for each selection
+    pub sequences: Arc<watch::Sender<HashMap<Selection, u64>>>,
+    /// The running watcher, for `watch.pause` and `watch.resume`
+    pub watch: Option<WatchControl>,
+    /// Offered as `plugin.<name>` tools
+    pub plugins: PluginHost,
+    /// Whether `clipboard.set` is allowed at all (`--write`)
+    pub allow_write: bool,
+    /// Whether `clipboard.set` may read a file the client names. Only over
+    /// stdio, where the client already runs as this user; over HTTP anyone
+    /// who reaches the port could read any file back through the clipboard.
+    pub file_access: bool,
 }
 
+impl McpServerState {
+    /// The latest sequence number seen for `selection`, 0 before any change.
+    pub fn sequence(&self, selection: Selection) -> u64 {
+        self.sequences
+            .borrow()
+            .get(&selection)
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+const DEFAULT_WAIT_TIMEOUT_MS: u64 = 30_000;
+const MAX_WAIT_TIMEOUT_MS: u64 = 300_000;
+
 #[derive(Debug, Deserialize)]
 pub struct AuthQuery {
     token: Option<String>,
@@ -52,20 +92,70 @@ impl McpServer {
             clipboard,
             file_manager,
             auth_manager: Arc::new(auth_manager),
-            initialized: Arc::new(RwLock::new(false)),
+            sessions: Arc::new(SessionManager::new()),
+            events: EventBus::new(),
+            sequences: Arc::new(watch::channel(HashMap::new()).0),
+            watch: None,
+            plugins: PluginHost::default(),
+            allow_write: false,
+            file_access: false,
         };
 
         Ok(Self { state, port, host })
     }
 
-    pub async fn run(self) -> Result<()> {
-        let app = Router::new()
+    /// Streams the watcher's clipboard events to `/sse` clients and pushes
+    /// them to MCP sessions as notifications.
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.state.events = events;
+        resources::spawn_notifier(self.state.clone());
+        self
+    }
+
+    /// Lets MCP clients pause, resume and inspect the watcher.
+    pub fn with_watch_control(mut self, control: WatchControl) -> Self {
+        self.state.watch = Some(control);
+        self
+    }
+
+    /// Offers the plugins as MCP tools.
+    pub fn with_plugins(mut self, plugins: PluginHost) -> Self {
+        self.state.plugins = plugins;
+        self
+    }
+
+    /// Allows `clipboard.set`; without this it is refused.
+    pub fn with_writes(mut self, allow_write: bool) -> Self {
+        self.state.allow_write = allow_write;
+        self
+    }
+
+    pub fn router(&self) -> Router {
+        Router::new()
             .route("/health", get(health_handler))
             .route("/", post(jsonrpc_handler))
             .route("/rpc", post(jsonrpc_handler))
             .route("/sse", get(sse_handler))
-            .layer(CorsLayer::permissive())
-            .with_state(self.state);
+            // Streamable HTTP transport
+            .route(
+                "/mcp",
+                post(mcp_post_handler)
+                    .get(mcp_get_handler)
+                    .delete(mcp_delete_handler),
+            )
+            // Pages served from this machine only, like the Origin check
+            .layer(
+                CorsLayer::new()
+                    .allow_origin(AllowOrigin::predicate(|origin, _| is_local_origin(origin)))
+                    .allow_methods(Any)
+                    .allow_headers(Any)
+                    .expose_headers([HeaderName::from_static(SESSION_HEADER)]),
+            )
+            .with_state(self.state.clone())
+    }
+
+    pub async fn run(self) -> Result<()> {
+        let app = self.router();
 
         let addr = format!("{}:{}", self.host, self.port);
         let listener = tokio::net::TcpListener::bind(&addr)
@@ -80,6 +170,23 @@ impl McpServer {
 
         Ok(())
     }
+
+    /// Serves MCP over stdin/stdout, as launched by `.mcp.json` configs.
+    pub async fn run_stdio(self) -> Result<()> {
+        info!("MCP server listening on stdio");
+
+        self.serve_stdio(tokio::io::stdin(), tokio::io::stdout())
+            .await
+    }
+
+    /// Serves newline-delimited JSON-RPC over any pair of byte streams.
+    pub async fn serve_stdio<R, W>(self, reader: R, writer: W) -> Result<()>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        super::stdio::serve(self.state, reader, writer).await
+    }
 }
 
 // Health check endpoint
@@ -91,6 +198,7 @@ async fn health_handler(State(state): State<McpServerState>) -> impl IntoRespons
         "version": env!("CARGO_PKG_VERSION"),
         "platform": std::env::consts::OS,
         "capabilities": ["text", "image", "watch"],
+        "backend": state.clipboard.backend_name(),
         "auth_required": token.is_some(),
     }))
 }
@@ -102,21 +210,8 @@ async fn jsonrpc_handler(
     Json(request): Json<Value>,
 ) -> Response {
     // Check authentication
-    let auth_header = headers
-        .get(header::AUTHORIZATION)
-        .and_then(|h| h.to_str().ok())
-        .and_then(|h| h.strip_prefix("Bearer "));
-
-    if !state.auth_manager.validate_token(auth_header).await {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(create_error_response(
-                None,
-                -32000,
-                "Authentication required".to_string(),
-            )),
-        )
-            .into_response();
+    if !is_authorized(&state, &headers).await {
+        return unauthorized_response();
     }
 
     // Handle batch requests
@@ -126,7 +221,7 @@ async fn jsonrpc_handler(
 
         for req in requests {
             if let Ok(rpc_req) = serde_json::from_value::<JsonRpcRequest>(req.clone()) {
-                responses.push(handle_single_request(state.clone(), rpc_req).await);
+                responses.push(handle_single_request(state.clone(), None, rpc_req).await);
             }
         }
 
@@ -142,7 +237,7 @@ async fn jsonrpc_handler(
     // Handle single request
     match serde_json::from_value::<JsonRpcRequest>(request) {
         Ok(rpc_req) => {
-            let response = handle_single_request(state, rpc_req).await;
+            let response = handle_single_request(state, None, rpc_req).await;
             Json(response).into_response()
         }
         Err(_) => Json(create_error_response(
@@ -154,12 +249,239 @@ async fn jsonrpc_handler(
     }
 }
 
-async fn handle_single_request(state: McpServerState, request: JsonRpcRequest) -> JsonRpcResponse {
+// Streamable HTTP: client messages, answered as JSON or as a short SSE stream
+async fn mcp_post_handler(
+    State(state): State<McpServerState>,
+    headers: HeaderMap,
+    Json(message): Json<Value>,
+) -> Response {
+    if !is_allowed_origin(&headers) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if !is_authorized(&state, &headers).await {
+        return unauthorized_response();
+    }
+
+    let messages = match message {
+        Value::Array(batch) => batch,
+        single => vec![single],
+    };
+
+    let mut requests = Vec::with_capacity(messages.len());
+    for message in messages {
+        match serde_json::from_value::<JsonRpcRequest>(message) {
+            Ok(request) => requests.push(request),
+            Err(_) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(create_error_response(
+                        None,
+                        INVALID_REQUEST,
+                        "Invalid JSON-RPC request".to_string(),
+                    )),
+                )
+                    .into_response()
+            }
+        }
+    }
+
+    let session = if requests.iter().any(|r| r.method == INITIALIZE) {
+        state.sessions.create().await
+    } else {
+        match find_session(&state, &headers).await {
+            Ok(session) => session,
+            Err(status) => return status.into_response(),
+        }
+    };
+    let session_header = session_header(&session);
+
+    // Notifications need no reply
+    if requests.iter().all(|r| r.id.is_none()) {
+        for request in requests {
+            handle_single_request(state.clone(), Some(session.clone()), request).await;
+        }
+        return (StatusCode::ACCEPTED, [session_header]).into_response();
+    }
+
+    // Tool calls may run for a while, so stream them when the client allows it
+    let stream_response =
+        accepts_event_stream(&headers) && requests.iter().any(|r| r.method == TOOLS_CALL);
+
+    if stream_response {
+        let stream = async_stream::stream! {
+            for request in requests {
+                let is_request = request.id.is_some();
+                let response = handle_single_request(state.clone(), Some(session.clone()), request).await;
+                if is_request {
+                    yield Ok::<_, anyhow::Error>(Event::default()
+                        .event("message")
+                        .data(serde_json::to_string(&response).unwrap()));
+                }
+            }
+        };
+
+        return ([session_header], Sse::new(stream)).into_response();
+    }
+
+    let batch = requests.len() > 1;
+    let mut responses = Vec::new();
+    for request in requests {
+        let is_request = request.id.is_some();
+        let response = handle_single_request(state.clone(), Some(session.clone()), request).await;
+        if is_request {
+            responses.push(serde_json::to_value(response).unwrap());
+        }
+    }
+
+    let body = if batch {
+        Value::Array(responses)
+    } else {
+        responses.pop().unwrap_or(Value::Null)
+    };
+
+    ([session_header], Json(body)).into_response()
+}
+
+// Streamable HTTP: long-lived stream for server-initiated messages
+async fn mcp_get_handler(State(state): State<McpServerState>, headers: HeaderMap) -> Response {
+    if !is_allowed_origin(&headers) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if !is_authorized(&state, &headers).await {
+        return unauthorized_response();
+    }
+
+    if !accepts_event_stream(&headers) {
+        return StatusCode::METHOD_NOT_ALLOWED.into_response();
+    }
+
+    let session = match find_session(&state, &headers).await {
+        Ok(session) => session,
+        Err(status) => return status.into_response(),
+    };
+
+    let mut outbound = session.subscribe();
+    let stream = async_stream::stream! {
+        loop {
+            match outbound.recv().await {
+                Ok(message) => {
+                    yield Ok::<_, anyhow::Error>(Event::default()
+                        .event("message")
+                        .data(message.to_string()));
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("MCP session stream lagged, dropped {} messages", skipped);
+                }
+                // Session was deleted
+                Err(RecvError::Closed) => break,
+            }
+        }
+    };
+
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+// Streamable HTTP: explicit session termination
+async fn mcp_delete_handler(State(state): State<McpServerState>, headers: HeaderMap) -> Response {
+    if !is_allowed_origin(&headers) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if !is_authorized(&state, &headers).await {
+        return unauthorized_response();
+    }
+
+    match headers.get(SESSION_HEADER).and_then(|h| h.to_str().ok()) {
+        Some(id) if state.sessions.remove(id).await => StatusCode::OK.into_response(),
+        Some(_) => StatusCode::NOT_FOUND.into_response(),
+        None => StatusCode::BAD_REQUEST.into_response(),
+    }
+}
+
+async fn is_authorized(state: &McpServerState, headers: &HeaderMap) -> bool {
+    let auth_header = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+
+    state.auth_manager.validate_token(auth_header).await
+}
+
+/// Browsers say which page a request comes from; only pages served from this
+/// machine may talk to the server, so a web page cannot drive it. Clients
+/// other than browsers send no `Origin`.
+fn is_allowed_origin(headers: &HeaderMap) -> bool {
+    headers.get(header::ORIGIN).is_none_or(is_local_origin)
+}
+
+fn is_local_origin(origin: &HeaderValue) -> bool {
+    origin
+        .to_str()
+        .ok()
+        .and_then(|origin| url::Url::parse(origin).ok())
+        .is_some_and(|origin| {
+            matches!(origin.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"))
+        })
+}
+
+fn unauthorized_response() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        Json(create_error_response(
+            None,
+            -32000,
+            "Authentication required".to_string(),
+        )),
+    )
+        .into_response()
+}
+
+fn accepts_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/event-stream"))
+}
+
+async fn find_session(
+    state: &McpServerState,
+    headers: &HeaderMap,
+) -> std::result::Result<Arc<Session>, StatusCode> {
+    let id = headers
+        .get(SESSION_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    state.sessions.get(id).await.ok_or(StatusCode::NOT_FOUND)
+}
+
+fn session_header(session: &Session) -> (HeaderName, HeaderValue) {
+    (
+        HeaderName::from_static(SESSION_HEADER),
+        HeaderValue::from_str(&session.id).expect("session ids are hex"),
+    )
+}
+
+pub(crate) async fn handle_single_request(
+    state: McpServerState,
+    session: Option<Arc<Session>>,
+    request: JsonRpcRequest,
+) -> JsonRpcResponse {
     match request.method.as_str() {
         INITIALIZE => handle_initialize(state, request).await,
-        INITIALIZED => handle_initialized(state, request).await,
+        INITIALIZED | NOTIFICATIONS_INITIALIZED => handle_initialized(session, request),
+        PING => create_success_response(request.id, json!({})),
         TOOLS_LIST => handle_tools_list(state, request).await,
         TOOLS_CALL => handle_tools_call(state, request).await,
+        RESOURCES_LIST => resources::handle_resources_list(request).await,
+        RESOURCES_READ => resources::handle_resources_read(state, request).await,
+        RESOURCES_SUBSCRIBE => {
+            resources::handle_resources_subscribe(state, session, request, true).await
+        }
+        RESOURCES_UNSUBSCRIBE => {
+            resources::handle_resources_subscribe(state, session, request, false).await
+        }
         _ => create_error_response(
             request.id,
             METHOD_NOT_FOUND,
@@ -169,11 +491,26 @@ async fn handle_single_request(state: McpServerState, request: JsonRpcRequest) -
 }
 
 async fn handle_initialize(_state: McpServerState, request: JsonRpcRequest) -> JsonRpcResponse {
+    // Echo the client's revision when we speak it, otherwise offer our latest
+    let requested = request
+        .params
+        .clone()
+        .and_then(|p| serde_json::from_value::<InitializeRequest>(p).ok())
+        .map(|p| p.protocol_version);
+
+    let protocol_version = match requested {
+        Some(version) if SUPPORTED_PROTOCOL_VERSIONS.contains(&version.as_str()) => version,
+        _ => PROTOCOL_VERSION.to_string(),
+    };
+
     let response = InitializeResponse {
-        protocol_version: "1.0".to_string(),
+        protocol_version,
         capabilities: ServerCapabilities {
             tools: Some(ToolsCapability {}),
-            resources: None,
+            resources: Some(ResourcesCapability {
+                subscribe: Some(true),
+                list_changed: Some(false),
+            }),
             prompts: None,
         },
         server_info: Some(ServerInfo {
@@ -185,25 +522,87 @@ async fn handle_initialize(_state: McpServerState, request: JsonRpcRequest) -> J
     create_success_response(request.id, serde_json::to_value(response).unwrap())
 }
 
-async fn handle_initialized(state: McpServerState, request: JsonRpcRequest) -> JsonRpcResponse {
-    *state.initialized.write().await = true;
-    info!("MCP server initialized");
+fn handle_initialized(session: Option<Arc<Session>>, request: JsonRpcRequest) -> JsonRpcResponse {
+    if let Some(session) = session {
+        session.mark_initialized();
+        info!("MCP session initialized: {}", session.id);
+    }
     create_success_response(request.id, json!({}))
 }
 
-async fn handle_tools_list(_state: McpServerState, request: JsonRpcRequest) -> JsonRpcResponse {
-    let tools = vec![
+async fn handle_tools_list(state: McpServerState, request: JsonRpcRequest) -> JsonRpcResponse {
+    let mut tools = vec![
         Tool {
             name: "clipboard.get".to_string(),
-            description: "Get current clipboard content (text or image)".to_string(),
+            description: "Get current clipboard content (text, rich text or image)".to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
                     "format": {
                         "type": "string",
-                        "enum": ["auto", "text", "image"],
-                        "description": "Preferred format (auto detects automatically)",
+                        "enum": ["auto", "text", "image", "html", "markdown"],
+                        "description": "Preferred format (auto detects automatically and renders rich text as Markdown)",
                         "default": "auto"
+                    },
+                    "mime": {
+                        "type": "string",
+                        "description": "Return exactly this target (see clipboard.list_formats) instead of detecting the content type"
+                    },
+                    "selection": {
+                        "type": "string",
+                        "enum": ["clipboard", "primary", "secondary"],
+                        "description": "Selection to read; primary and secondary exist on X11/Wayland only",
+                        "default": "clipboard"
+                    }
+                },
+                "required": []
+            }),
+        },
+        Tool {
+            name: "clipboard.wait".to_string(),
+            description: "Wait until the clipboard changes, then return it like clipboard.get"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "after": {
+                        "type": "integer",
+                        "description": "Sequence number from an earlier clipboard.get or clipboard.wait; returns as soon as the clipboard has changed since (default: wait for the next change)"
+                    },
+                    "timeout_ms": {
+                        "type": "integer",
+                        "description": "Give up after this many milliseconds, returning changed: false (at most 300000)",
+                        "default": DEFAULT_WAIT_TIMEOUT_MS
+                    },
+                    "format": {
+                        "type": "string",
+                        "enum": ["auto", "text", "image", "html", "markdown"],
+                        "default": "auto"
+                    },
+                    "mime": {
+                        "type": "string"
+                    },
+                    "selection": {
+                        "type": "string",
+                        "enum": ["clipboard", "primary", "secondary"],
+                        "default": "clipboard"
+                    }
+                },
+                "required": []
+            }),
+        },
+        Tool {
+            name: "clipboard.list_formats".to_string(),
+            description: "List every MIME target the clipboard owner offers, with sizes where known"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "selection": {
+                        "type": "string",
+                        "enum": ["clipboard", "primary", "secondary"],
+                        "description": "Selection to inspect; primary and secondary exist on X11/Wayland only",
+                        "default": "clipboard"
                     }
                 },
                 "required": []
@@ -217,19 +616,64 @@ async fn handle_tools_list(_state: McpServerState, request: JsonRpcRequest) -> J
                 "properties": {
                     "type": {
                         "type": "string",
-                        "enum": ["text/plain", "image/png"],
-                        "description": "Content type"
+                        "enum": ["text/plain", "text/html", "image/png", "image/jpeg", "image/webp"],
+                        "description": "Content type (guessed from the file extension when omitted)"
                     },
                     "data": {
                         "type": "string",
                         "description": "Content data (text or base64 for images)"
+                    },
+                    "file": {
+                        "type": "string",
+                        "description": "Path of a file to copy instead of inline data (stdio only)"
+                    },
+                    "selection": {
+                        "type": "string",
+                        "enum": ["clipboard", "primary", "secondary"],
+                        "description": "Selection to write; primary and secondary exist on X11/Wayland only",
+                        "default": "clipboard"
                     }
                 },
-                "required": ["type", "data"]
+                "required": []
             }),
         },
+        Tool {
+            name: "watch.pause".to_string(),
+            description: "Pause clipboard capture; nothing copied while paused is reported or staged"
+                .to_string(),
+            input_schema: json!({"type": "object", "properties": {}, "required": []}),
+        },
+        Tool {
+            name: "watch.resume".to_string(),
+            description: "Resume clipboard capture after watch.pause".to_string(),
+            input_schema: json!({"type": "object", "properties": {}, "required": []}),
+        },
+        Tool {
+            name: "watch.status".to_string(),
+            description: "Whether the clipboard watcher is paused, how it detects changes and its poll interval"
+                .to_string(),
+            input_schema: json!({"type": "object", "properties": {}, "required": []}),
+        },
     ];
 
+    tools.extend(state.plugins.tools().map(|plugin| Tool {
+        name: plugin.tool_name(),
+        description: plugin.description(),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "selection": {
+                    "type": "string",
+                    "enum": ["clipboard", "primary", "secondary"],
+                    "description": "Selection to run the plugin on",
+                    "default": "clipboard"
+                }
+            },
+            "additionalProperties": true,
+            "required": []
+        }),
+    }));
+
     let response = ToolListResponse { tools };
     create_success_response(request.id, serde_json::to_value(response).unwrap())
 }
@@ -260,6 +704,16 @@ async fn handle_tools_call(state: McpServerState, request: JsonRpcRequest) -> Js
     match tool_request.name.as_str() {
         "clipboard.get" => handle_clipboard_get(state, request.id, tool_request.arguments).await,
         "clipboard.set" => handle_clipboard_set(state, request.id, tool_request.arguments).await,
+        "clipboard.wait" => handle_clipboard_wait(state, request.id, tool_request.arguments).await,
+        "clipboard.list_formats" => {
+            handle_clipboard_list_formats(state, request.id, tool_request.arguments).await
+        }
+        "watch.pause" | "watch.resume" | "watch.status" => {
+            handle_watch_control(state, request.id, &tool_request.name)
+        }
+        name if name.starts_with("plugin.") => {
+            handle_plugin_tool(state, request.id, name, tool_request.arguments).await
+        }
         _ => create_error_response(
             request.id,
             METHOD_NOT_FOUND,
@@ -268,19 +722,82 @@ async fn handle_tools_call(state: McpServerState, request: JsonRpcRequest) -> Js
     }
 }
 
+#[derive(Deserialize, Default)]
+struct GetArgs {
+    #[serde(default)]
+    format: Option<String>,
+    #[serde(default)]
+    mime: Option<String>,
+    #[serde(default)]
+    selection: Option<String>,
+}
+
 async fn handle_clipboard_get(
     state: McpServerState,
     id: Option<Value>,
-    _args: Option<Value>,
+    args: Option<Value>,
 ) -> JsonRpcResponse {
-    // Get clipboard content
-    let clipboard_data = match state.clipboard.get_content() {
+    let args: GetArgs = args
+        .and_then(|a| serde_json::from_value(a).ok())
+        .unwrap_or_default();
+
+    let selection = match parse_selection(args.selection.as_deref()) {
+        Ok(selection) => selection,
+        Err(message) => return create_error_response(id, INVALID_PARAMS, message),
+    };
+
+    let response_data = match read_clipboard(&state, selection, &args).await {
         Ok(data) => data,
-        Err(e) => {
-            return create_error_response(id, INTERNAL_ERROR, format!("Clipboard error: {e}"))
-        }
+        Err((code, message)) => return create_error_response(id, code, message),
+    };
+
+    let tool_response = ToolCallResponse {
+        content: vec![Content::Text {
+            text: serde_json::to_string_pretty(&response_data).unwrap(),
+        }],
+    };
+
+    create_success_response(id, serde_json::to_value(tool_response).unwrap())
+}
+
+/// What `clipboard.get` and `clipboard.wait` return: the content, staged
+/// when too large to inline, its metadata and the watcher's sequence number.
+async fn read_clipboard(
+    state: &McpServerState,
+    selection: Selection,
+    args: &GetArgs,
+) -> std::result::Result<Value, (i32, String)> {
+    // Read the sequence first, so a change during the read is waited for
+    let sequence = state.sequence(selection);
+
+    // Get clipboard content. Anything too large to inline is staged from
+    // what was read here, the target's bytes or the snapshot, rather than
+    // read again from a clipboard that may have moved on
+    let (result, target, snapshot) = match &args.mime {
+        Some(mime) => match state.clipboard.read_target(selection, mime) {
+            Ok(bytes) => (
+                Ok(state.clipboard.describe_target(selection, mime, &bytes)),
+                Some(bytes),
+                None,
+            ),
+            Err(e) => (Err(e), None, None),
+        },
+        None => match state.clipboard.read_content(selection) {
+            Ok((snapshot, data)) => (Ok(data), None, Some(snapshot)),
+            Err(e) => (Err(e), None, None),
+        },
     };
 
+    let mut clipboard_data =
+        result.map_err(|e| (INTERNAL_ERROR, format!("Clipboard error: {e}")))?;
+
+    // An explicit target is returned exactly as offered
+    if args.mime.is_none() {
+        let format = args.format.as_deref().unwrap_or("auto");
+        clipboard_data.content = apply_format(clipboard_data.content, format)
+            .map_err(|message| (INVALID_PARAMS, message))?;
+    }
+
     // Handle image staging if needed
     let final_content = match &clipboard_data.content {
         ClipboardContent::ImagePng {
@@ -297,10 +814,10 @@ async fn handle_clipboard_get(
             size,
             ..
         } => {
-            // Need to stage the image
-            match state.clipboard.get_raw_image() {
-                Ok(image_data) => {
-                    match state.file_manager.stage_image(&image_data, "png").await {
+            // Need to stage the image, encoded once when it was captured
+            match snapshot.as_ref().and_then(|snapshot| snapshot.png()) {
+                Some(png) => {
+                    match state.file_manager.stage_image(png, "png").await {
                         Ok(staged) => {
                             // Update content with file path
                             match clipboard_data.content {
@@ -327,19 +844,224 @@ async fn handle_clipboard_get(
                         }
                     }
                 }
-                Err(e) => {
-                    error!("Failed to get raw image: {}", e);
+                None => {
+                    error!("Failed to stage image: not in the snapshot");
                     clipboard_data.content.clone()
                 }
             }
         }
+        ClipboardContent::Files { files } => ClipboardContent::Files {
+            files: resolve_files(state, files).await,
+        },
+        ClipboardContent::Target {
+            mime,
+            data: None,
+            size,
+            ..
+        } => match stage_target(state, mime, target.as_deref().unwrap_or_default()).await {
+            Ok(file) => ClipboardContent::Target {
+                mime: mime.clone(),
+                data: None,
+                encoding: None,
+                file: Some(file),
+                size: *size,
+            },
+            Err(e) => {
+                error!("Failed to stage {}: {}", mime, e);
+                clipboard_data.content.clone()
+            }
+        },
         _ => clipboard_data.content.clone(),
     };
 
-    // Create response
-    let response_data = json!({
+    Ok(json!({
         "content": final_content,
         "metadata": clipboard_data.metadata,
+        "sequence": sequence,
+    }))
+}
+
+async fn handle_clipboard_wait(
+    state: McpServerState,
+    id: Option<Value>,
+    args: Option<Value>,
+) -> JsonRpcResponse {
+    #[derive(Deserialize, Default)]
+    struct WaitArgs {
+        #[serde(default)]
+        after: Option<u64>,
+        #[serde(default)]
+        timeout_ms: Option<u64>,
+        #[serde(flatten)]
+        get: GetArgs,
+    }
+
+    let args: WaitArgs = match args.map(serde_json::from_value).transpose() {
+        Ok(args) => args.unwrap_or_default(),
+        Err(e) => {
+            return create_error_response(id, INVALID_PARAMS, format!("Invalid arguments: {e}"))
+        }
+    };
+
+    let selection = match parse_selection(args.get.selection.as_deref()) {
+        Ok(selection) => selection,
+        Err(message) => return create_error_response(id, INVALID_PARAMS, message),
+    };
+
+    // Nothing changes for a watcher that is not running yet
+    if let Some(control) = &state.watch {
+        control.activate();
+    }
+
+    // Without `after`, wait for the next change from now
+    let after = args.after.unwrap_or_else(|| state.sequence(selection));
+    let timeout = Duration::from_millis(
+        args.timeout_ms
+            .unwrap_or(DEFAULT_WAIT_TIMEOUT_MS)
+            .min(MAX_WAIT_TIMEOUT_MS),
+    );
+
+    let mut sequences = state.sequences.subscribe();
+    let changed = tokio::time::timeout(
+        timeout,
+        sequences.wait_for(|sequences| sequences.get(&selection).is_some_and(|&s| s > after)),
+    )
+    .await
+    .is_ok_and(|result| result.is_ok());
+
+    let response_data = if changed {
+        match read_clipboard(&state, selection, &args.get).await {
+            Ok(mut data) => {
+                data["changed"] = json!(true);
+                data
+            }
+            Err((code, message)) => return create_error_response(id, code, message),
+        }
+    } else {
+        json!({
+            "changed": false,
+            "sequence": state.sequence(selection),
+        })
+    };
+
+    let tool_response = ToolCallResponse {
+        content: vec![Content::Text {
+            text: serde_json::to_string_pretty(&response_data).unwrap(),
+        }],
+    };
+
+    create_success_response(id, serde_json::to_value(tool_response).unwrap())
+}
+
+fn handle_watch_control(state: McpServerState, id: Option<Value>, tool: &str) -> JsonRpcResponse {
+    let Some(control) = &state.watch else {
+        return create_error_response(
+            id,
+            INTERNAL_ERROR,
+            "Clipboard watcher is not running".to_string(),
+        );
+    };
+
+    match tool {
+        "watch.pause" => control.pause(),
+        "watch.resume" => control.resume(),
+        _ => {}
+    }
+
+    let tool_response = ToolCallResponse {
+        content: vec![Content::Text {
+            text: serde_json::to_string_pretty(&control.status()).unwrap(),
+        }],
+    };
+
+    create_success_response(id, serde_json::to_value(tool_response).unwrap())
+}
+
+/// Runs a plugin on the selection and returns its output. The clipboard is
+/// left as it is.
+async fn handle_plugin_tool(
+    state: McpServerState,
+    id: Option<Value>,
+    tool: &str,
+    args: Option<Value>,
+) -> JsonRpcResponse {
+    if state.plugins.tool(tool).is_none() {
+        return create_error_response(id, METHOD_NOT_FOUND, format!("Unknown tool: {tool}"));
+    }
+
+    let arguments = args.unwrap_or_else(|| json!({}));
+    let selection = match parse_selection(arguments.get("selection").and_then(Value::as_str)) {
+        Ok(selection) => selection,
+        Err(message) => return create_error_response(id, INVALID_PARAMS, message),
+    };
+
+    let data = match state.clipboard.get_content_from(selection) {
+        Ok(data) => data,
+        Err(e) => {
+            return create_error_response(id, INTERNAL_ERROR, format!("Clipboard error: {e}"))
+        }
+    };
+
+    let input = PluginInput {
+        stage: PluginStage::Tool,
+        data,
+        arguments,
+    };
+    let output = match state.plugins.call_tool(tool, input).await {
+        Ok(output) => output,
+        Err(e) => return create_error_response(id, INTERNAL_ERROR, e.to_string()),
+    };
+
+    let tool_response = ToolCallResponse {
+        content: vec![Content::Text {
+            text: serde_json::to_string_pretty(&output).unwrap(),
+        }],
+    };
+
+    create_success_response(id, serde_json::to_value(tool_response).unwrap())
+}
+
+/// Writes a target too large to inline to the staging directory.
+async fn stage_target(state: &McpServerState, mime: &str, bytes: &[u8]) -> Result<String> {
+    let extension = mime_guess::get_mime_extensions_str(mime)
+        .and_then(|extensions| extensions.first())
+        .copied()
+        .unwrap_or("bin");
+
+    let staged = state.file_manager.stage_data(bytes, extension).await?;
+    Ok(staged.path.to_string_lossy().to_string())
+}
+
+async fn handle_clipboard_list_formats(
+    state: McpServerState,
+    id: Option<Value>,
+    args: Option<Value>,
+) -> JsonRpcResponse {
+    #[derive(Deserialize, Default)]
+    struct ListFormatsArgs {
+        #[serde(default)]
+        selection: Option<String>,
+    }
+
+    let args: ListFormatsArgs = args
+        .and_then(|a| serde_json::from_value(a).ok())
+        .unwrap_or_default();
+
+    let selection = match parse_selection(args.selection.as_deref()) {
+        Ok(selection) => selection,
+        Err(message) => return create_error_response(id, INVALID_PARAMS, message),
+    };
+
+    let formats = match state.clipboard.list_formats(selection) {
+        Ok(formats) => formats,
+        Err(e) => {
+            return create_error_response(id, INTERNAL_ERROR, format!("Clipboard error: {e}"))
+        }
+    };
+
+    let response_data = json!({
+        "selection": selection,
+        "formats": formats,
     });
 
     let tool_response = ToolCallResponse {
@@ -351,17 +1073,101 @@ async fn handle_clipboard_get(
     create_success_response(id, serde_json::to_value(tool_response).unwrap())
 }
 
+/// Inlines small text files and stages images so copied files read like
+/// copied content.
+async fn resolve_files(state: &McpServerState, files: &[FileEntry]) -> Vec<FileEntry> {
+    let mut resolved = Vec::with_capacity(files.len());
+
+    for entry in files {
+        let mut entry = entry.clone();
+
+        if entry.is_text() && entry.size as usize <= crate::MAX_INLINE_SIZE {
+            match tokio::fs::read(&entry.path).await {
+                Ok(bytes) => entry.data = String::from_utf8(bytes).ok(),
+                Err(e) => warn!("Failed to read copied file {}: {}", entry.path, e),
+            }
+        } else if entry.is_image() {
+            let extension = std::path::Path::new(&entry.path)
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or("png")
+                .to_lowercase();
+
+            match tokio::fs::read(&entry.path).await {
+                Ok(bytes) => match state.file_manager.stage_image(&bytes, &extension).await {
+                    Ok(staged) => entry.file = Some(staged.path.to_string_lossy().to_string()),
+                    Err(e) => error!("Failed to stage image: {}", e),
+                },
+                Err(e) => warn!("Failed to read copied file {}: {}", entry.path, e),
+            }
+        }
+
+        resolved.push(entry);
+    }
+
+    resolved
+}
+
+fn parse_selection(selection: Option<&str>) -> std::result::Result<Selection, String> {
+    selection.map_or(Ok(Selection::Clipboard), str::parse)
+}
+
+/// Shapes clipboard content to the `format` requested by `clipboard.get`.
+fn apply_format(
+    content: ClipboardContent,
+    format: &str,
+) -> std::result::Result<ClipboardContent, String> {
+    match (format, content) {
+        // Markdown keeps rich text structure while staying readable
+        ("auto" | "markdown", content) => Ok(content.into_markdown()),
+        ("html", content) => Ok(content),
+        (
+            "text",
+            ClipboardContent::Html {
+                data,
+                alt_text,
+                truncated,
+            },
+        ) => Ok(ClipboardContent::Text {
+            data: alt_text.unwrap_or_else(|| html_to_markdown(&data)),
+            truncated,
+        }),
+        ("text", content @ (ClipboardContent::Text { .. } | ClipboardContent::Markdown { .. })) => {
+            Ok(content)
+        }
+        ("text", _) => Err("Clipboard does not contain text".to_string()),
+        (
+            "image",
+            content @ (ClipboardContent::ImagePng { .. } | ClipboardContent::ImageJpeg { .. }),
+        ) => Ok(content),
+        ("image", _) => Err("Clipboard does not contain an image".to_string()),
+        (other, _) => Err(format!("Unsupported format: {other}")),
+    }
+}
+
 async fn handle_clipboard_set(
     state: McpServerState,
     id: Option<Value>,
     args: Option<Value>,
 ) -> JsonRpcResponse {
-    // TODO: Check for --write flag permission
+    if !state.allow_write {
+        return create_error_response(
+            id,
+            INVALID_REQUEST,
+            "clipboard.set is disabled; start the server with --write".to_string(),
+        );
+    }
 
     #[derive(Deserialize)]
     struct SetArgs {
-        r#type: String,
-        data: String,
+        #[serde(default)]
+        r#type: Option<String>,
+        #[serde(default)]
+        data: Option<String>,
+        #[serde(default)]
+        file: Option<String>,
+        #[serde(default)]
+        selection: Option<String>,
     }
 
     let args: SetArgs = match args.and_then(|a| serde_json::from_value(a).ok()) {
@@ -369,28 +1175,36 @@ async fn handle_clipboard_set(
         None => return create_error_response(id, INVALID_PARAMS, "Invalid arguments".to_string()),
     };
 
-    let content = match args.r#type.as_str() {
-        "text/plain" => ClipboardContent::Text {
-            data: args.data,
-            truncated: None,
-        },
-        "image/png" => ClipboardContent::ImagePng {
-            data: Some(args.data),
-            file: None,
-            width: 0, // Will be updated by clipboard manager
-            height: 0,
-            size: 0,
-        },
-        _ => {
-            return create_error_response(
-                id,
-                INVALID_PARAMS,
-                format!("Unsupported type: {}", args.r#type),
-            )
+    let selection = match parse_selection(args.selection.as_deref()) {
+        Ok(selection) => selection,
+        Err(message) => return create_error_response(id, INVALID_PARAMS, message),
+    };
+
+    let mime = match (args.r#type, &args.file) {
+        (Some(mime), _) => mime,
+        (None, Some(file)) => mime_guess::from_path(file)
+            .first_or_text_plain()
+            .essence_str()
+            .to_string(),
+        (None, None) => {
+            return create_error_response(id, INVALID_PARAMS, "Missing type".to_string())
         }
     };
 
-    match state.clipboard.set_content(&content) {
+    if args.file.is_some() && !state.file_access {
+        return create_error_response(
+            id,
+            INVALID_PARAMS,
+            "The file argument is only accepted over stdio; send the data instead".to_string(),
+        );
+    }
+
+    let content = match set_args_content(&mime, args.data, args.file).await {
+        Ok(content) => content,
+        Err(message) => return create_error_response(id, INVALID_PARAMS, message),
+    };
+
+    match state.clipboard.set_content_to(selection, &content) {
         Ok(_) => {
             let tool_response = ToolCallResponse {
                 content: vec![Content::Text {
@@ -405,6 +1219,67 @@ async fn handle_clipboard_set(
     }
 }
 
+/// Builds what `clipboard.set` should write. Images stay encoded and are
+/// decoded, with their real dimensions, by the clipboard manager.
+async fn set_args_content(
+    mime: &str,
+    data: Option<String>,
+    file: Option<String>,
+) -> std::result::Result<ClipboardContent, String> {
+    if data.is_none() && file.is_none() {
+        return Err("Either data or file is required".to_string());
+    }
+
+    match mime {
+        "text/plain" | "text/html" => {
+            // Text variants carry no file reference, so read it here
+            let text = match (data, file) {
+                (Some(data), _) => data,
+                (None, Some(file)) => tokio::fs::read_to_string(&file)
+                    .await
+                    .map_err(|e| format!("Failed to read {file}: {e}"))?,
+                (None, None) => String::new(),
+            };
+
+            Ok(if mime == "text/html" {
+                ClipboardContent::Html {
+                    data: text,
+                    alt_text: None,
+                    truncated: None,
+                }
+            } else {
+                ClipboardContent::Text {
+                    data: text,
+                    truncated: None,
+                }
+            })
+        }
+        "image/png" => Ok(ClipboardContent::ImagePng {
+            data,
+            file,
+            width: 0,
+            height: 0,
+            size: 0,
+        }),
+        "image/jpeg" => Ok(ClipboardContent::ImageJpeg {
+            data,
+            file,
+            width: 0,
+            height: 0,
+            size: 0,
+        }),
+        // WebP, GIF, BMP, ...: anything the image decoder understands
+        image if image.starts_with("image/") => Ok(ClipboardContent::Target {
+            mime: mime.to_string(),
+            encoding: data.as_ref().map(|_| "base64".to_string()),
+            data,
+            file,
+            size: 0,
+        }),
+        other => Err(format!("Unsupported type: {other}")),
+    }
+}
+
 // SSE handler for real-time updates
 async fn sse_handler(
     State(state): State<McpServerState>,
@@ -419,12 +1294,28 @@ async fn sse_handler(
         return Err(StatusCode::UNAUTHORIZED);
     }
 
+    // A slow client only misses old changes; it never holds up the watcher
+    let mut events = state.events.subscribe("sse", 16, LagPolicy::DropOldest);
+
     let stream = async_stream::stream! {
         loop {
-            tokio::time::sleep(Duration::from_secs(30)).await;
-            yield Ok::<_, anyhow::Error>(Event::default()
-                .data("heartbeat")
-                .event("ping"));
+            tokio::select! {
+                event = events.recv() => {
+                    let Some(event) = event else { break };
+                    let payload = json!({
+                        "content": event.content,
+                        "mime_types": event.mime_types,
+                    });
+                    yield Ok::<_, anyhow::Error>(Event::default()
+                        .data(payload.to_string())
+                        .event("clipboard"));
+                }
+                _ = tokio::time::sleep(Duration::from_secs(30)) => {
+                    yield Ok(Event::default()
+                        .data("heartbeat")
+                        .event("ping"));
+                }
+            }
         }
     };
 
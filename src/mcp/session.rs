@@ -0,0 +1,192 @@
+use rand::Rng;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, RwLock};
+use tracing::{debug, info};
+
+pub const SESSION_HEADER: &str = "mcp-session-id";
+
+/// How long a session may go unused before it is dropped.
+pub const SESSION_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Sessions kept at once; the least recently used goes first.
+pub const MAX_SESSIONS: usize = 64;
+
+/// A Streamable HTTP session, created by `initialize` and ended by `DELETE`.
+/// The stdio transport runs as a single session for its lifetime.
+pub struct Session {
+    pub id: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    outbound: broadcast::Sender<Value>,
+    resources: Mutex<HashSet<String>>,
+    initialized: AtomicBool,
+    last_used: Mutex<Instant>,
+}
+
+impl Session {
+    fn new(id: String) -> Self {
+        let (outbound, _) = broadcast::channel(64);
+
+        Self {
+            id,
+            created_at: chrono::Utc::now(),
+            outbound,
+            resources: Mutex::new(HashSet::new()),
+            initialized: AtomicBool::new(false),
+            last_used: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Records the client's `notifications/initialized`.
+    pub fn mark_initialized(&self) {
+        self.initialized.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.initialized.load(Ordering::Relaxed)
+    }
+
+    fn touch(&self) {
+        if let Ok(mut last_used) = self.last_used.lock() {
+            *last_used = Instant::now();
+        }
+    }
+
+    fn last_used(&self) -> Instant {
+        self.last_used
+            .lock()
+            .map(|last_used| *last_used)
+            .unwrap_or_else(|_| Instant::now())
+    }
+
+    /// Unused for `ttl`, and with no stream open on it.
+    fn is_idle(&self, ttl: Duration) -> bool {
+        self.outbound.receiver_count() == 0 && self.last_used().elapsed() > ttl
+    }
+
+    /// Queues a server-initiated message for any open `GET` stream.
+    pub fn send(&self, message: Value) {
+        // No listeners just means the client never opened a stream
+        let _ = self.outbound.send(message);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Value> {
+        self.outbound.subscribe()
+    }
+
+    /// Asks for `notifications/resources/updated` whenever `uri` changes.
+    pub fn subscribe_resource(&self, uri: &str) {
+        if let Ok(mut resources) = self.resources.lock() {
+            resources.insert(uri.to_string());
+        }
+    }
+
+    pub fn unsubscribe_resource(&self, uri: &str) {
+        if let Ok(mut resources) = self.resources.lock() {
+            resources.remove(uri);
+        }
+    }
+
+    pub fn is_subscribed(&self, uri: &str) -> bool {
+        self.resources
+            .lock()
+            .map(|resources| resources.contains(uri))
+            .unwrap_or(false)
+    }
+}
+
+/// The open sessions. Clients that never send `DELETE` do not pile up:
+/// sessions unused for the TTL are dropped, and past the maximum the least
+/// recently used one makes way for a new one.
+pub struct SessionManager {
+    sessions: RwLock<HashMap<String, Arc<Session>>>,
+    ttl: Duration,
+    max_sessions: usize,
+}
+
+impl Default for SessionManager {
+    fn default() -> Self {
+        Self::with_limits(SESSION_TTL, MAX_SESSIONS)
+    }
+}
+
+impl SessionManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_limits(ttl: Duration, max_sessions: usize) -> Self {
+        Self {
+            sessions: RwLock::new(HashMap::new()),
+            ttl,
+            max_sessions: max_sessions.max(1),
+        }
+    }
+
+    pub async fn create(&self) -> Arc<Session> {
+        let session = Arc::new(Session::new(generate_session_id()));
+        let mut sessions = self.sessions.write().await;
+
+        sessions.retain(|id, session| {
+            let idle = session.is_idle(self.ttl);
+            if idle {
+                info!("MCP session expired: {}", id);
+            }
+            !idle
+        });
+        while sessions.len() >= self.max_sessions {
+            let Some(oldest) = sessions
+                .values()
+                // Sessions with a stream open are the last to go
+                .min_by_key(|session| (session.outbound.receiver_count() > 0, session.last_used()))
+                .map(|session| session.id.clone())
+            else {
+                break;
+            };
+            sessions.remove(&oldest);
+            info!("MCP session evicted: {}", oldest);
+        }
+        sessions.insert(session.id.clone(), session.clone());
+        drop(sessions);
+
+        info!("MCP session started: {}", session.id);
+        session
+    }
+
+    /// The session, if it is still open; counts as a use of it.
+    pub async fn get(&self, id: &str) -> Option<Arc<Session>> {
+        let session = self.sessions.read().await.get(id).cloned()?;
+        if session.is_idle(self.ttl) {
+            self.remove(id).await;
+            return None;
+        }
+
+        session.touch();
+        Some(session)
+    }
+
+    pub async fn remove(&self, id: &str) -> bool {
+        let removed = self.sessions.write().await.remove(id).is_some();
+        if removed {
+            info!("MCP session ended: {}", id);
+        } else {
+            debug!("Ignoring unknown MCP session: {}", id);
+        }
+        removed
+    }
+
+    pub async fn all(&self) -> Vec<Arc<Session>> {
+        self.sessions.read().await.values().cloned().collect()
+    }
+}
+
+fn generate_session_id() -> String {
+    // Visible ASCII only, as required for the header value
+    let mut rng = rand::thread_rng();
+    (0..16)
+        .map(|_| format!("{:02x}", rng.gen::<u8>()))
+        .collect()
+}
@@ -0,0 +1,147 @@
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::{
+    mcp::{protocol::*, server::handle_single_request, server::McpServerState, session::Session},
+    Result,
+};
+
+/// Runs the newline-delimited JSON-RPC loop until the reader hits EOF.
+///
+/// Each line is one message (or batch). Notifications are dispatched but
+/// never answered, as required by JSON-RPC. Every message is handled in its
+/// own task, so a `clipboard.wait` holds up nothing else, and replies are
+/// written as they are ready, in any order. The connection is one session,
+/// so server-initiated notifications are written between replies.
+pub async fn serve<R, W>(mut state: McpServerState, reader: R, mut writer: W) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    // The client launched us, so it may already read whatever we can
+    state.file_access = true;
+    let mut lines = BufReader::new(reader).lines();
+    let session = state.sessions.create().await;
+    let mut outbound = session.subscribe();
+    let (replies_tx, mut replies) = mpsc::unbounded_channel();
+
+    loop {
+        let reply = tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else { break };
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+
+                match serde_json::from_str::<Value>(line) {
+                    Ok(message) => {
+                        let state = state.clone();
+                        let session = session.clone();
+                        let replies = replies_tx.clone();
+                        tokio::spawn(async move {
+                            if let Some(reply) = handle_message(&state, &session, message).await {
+                                let _ = replies.send(reply);
+                            }
+                        });
+                        None
+                    }
+                    Err(e) => Some(serde_json::to_value(create_error_response(
+                        None,
+                        PARSE_ERROR,
+                        format!("Parse error: {e}"),
+                    ))?),
+                }
+            }
+            Some(reply) = replies.recv() => Some(reply),
+            message = outbound.recv() => match message {
+                Ok(message) => Some(message),
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("stdio client lagged, dropped {} notifications", skipped);
+                    None
+                }
+                Err(RecvError::Closed) => None,
+            },
+        };
+
+        if let Some(reply) = reply {
+            write_message(&mut writer, &reply).await?;
+        }
+    }
+
+    // Answer what was asked before stdin closed
+    drop(replies_tx);
+    while let Some(reply) = replies.recv().await {
+        write_message(&mut writer, &reply).await?;
+    }
+
+    state.sessions.remove(&session.id).await;
+    info!("stdin closed, shutting down MCP stdio transport");
+    Ok(())
+}
+
+async fn write_message<W: AsyncWrite + Unpin>(writer: &mut W, message: &Value) -> Result<()> {
+    let mut bytes = serde_json::to_vec(message)?;
+    bytes.push(b'\n');
+    writer.write_all(&bytes).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn handle_message(
+    state: &McpServerState,
+    session: &Arc<Session>,
+    message: Value,
+) -> Option<Value> {
+    // Handle batch requests
+    if let Value::Array(requests) = message {
+        let mut responses = Vec::new();
+
+        for req in requests {
+            if let Some(response) = handle_single_message(state, session, req).await {
+                responses.push(response);
+            }
+        }
+
+        return if responses.is_empty() {
+            None
+        } else {
+            Some(Value::Array(responses))
+        };
+    }
+
+    handle_single_message(state, session, message).await
+}
+
+async fn handle_single_message(
+    state: &McpServerState,
+    session: &Arc<Session>,
+    message: Value,
+) -> Option<Value> {
+    let request = match serde_json::from_value::<JsonRpcRequest>(message) {
+        Ok(request) => request,
+        Err(_) => {
+            return serde_json::to_value(create_error_response(
+                None,
+                INVALID_REQUEST,
+                "Invalid JSON-RPC request".to_string(),
+            ))
+            .ok()
+        }
+    };
+
+    let is_notification = request.id.is_none();
+    let method = request.method.clone();
+    let response = handle_single_request(state.clone(), Some(session.clone()), request).await;
+
+    if is_notification {
+        debug!("Handled notification: {}", method);
+        return None;
+    }
+
+    serde_json::to_value(response).ok()
+}
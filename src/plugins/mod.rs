@@ -0,0 +1,272 @@
+//! In-process WebAssembly plugins that transform, annotate or veto copies.
+//!
+//! A plugin is a core WebAssembly module (binary or text format) with no
+//! imports that exports:
+//!
+//! - `memory`
+//! - `alloc(len: i32) -> i32`, returning where the host may write `len`
+//!   bytes of input
+//! - `process(ptr: i32, len: i32) -> i64`, returning the output's offset in
+//!   the high 32 bits and its length in the low 32 bits
+//!
+//! The input is a JSON [`PluginInput`] and the output a JSON
+//! [`PluginOutput`]. Plugins run with a fuel budget and capped memory, and
+//! see nothing but their input. Running them needs the `plugins` feature.
+
+#[cfg(feature = "plugins")]
+mod wasm;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::clipboard::{ClipboardContent, ClipboardData};
+use crate::{ClaudeUtilsError, Result};
+
+/// Instructions a plugin may run per call unless its config says otherwise.
+pub const DEFAULT_FUEL: u64 = 100_000_000;
+
+/// One `[[plugins]]` entry of the config file.
+///
+/// ```toml
+/// [[plugins]]
+/// name = "redact"
+/// path = "/home/me/.claude-utils/plugins/redact.wasm"
+/// description = "Masks API keys and email addresses"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginConfig {
+    pub name: String,
+    pub path: PathBuf,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Run on every copy the watcher sees, before it is published
+    #[serde(default = "enabled")]
+    pub watch: bool,
+    /// Offer the plugin to MCP clients as the `plugin.<name>` tool
+    #[serde(default = "enabled")]
+    pub tool: bool,
+    #[serde(default = "default_fuel")]
+    pub fuel: u64,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn default_fuel() -> u64 {
+    DEFAULT_FUEL
+}
+
+/// Where a plugin is being run from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginStage {
+    Watch,
+    Tool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginInput {
+    pub stage: PluginStage,
+    pub data: ClipboardData,
+    /// The tool call's arguments; null in the watch pipeline
+    pub arguments: Value,
+}
+
+/// What a plugin hands back; every field is optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginOutput {
+    /// Replaces the content; text, Markdown or HTML only
+    pub content: Option<ClipboardContent>,
+    /// Merged into the metadata's `annotations`
+    pub metadata: Map<String, Value>,
+    /// Why the copy must not be reported
+    pub veto: Option<String>,
+}
+
+/// The outcome of running the watch pipeline over one copy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Pass,
+    /// A plugin replaced the content
+    Rewritten,
+    Veto {
+        plugin: String,
+        reason: String,
+    },
+}
+
+pub struct Plugin {
+    config: PluginConfig,
+    #[cfg(feature = "plugins")]
+    runtime: wasm::Runtime,
+}
+
+impl Plugin {
+    fn load(config: PluginConfig) -> Result<Self> {
+        #[cfg(feature = "plugins")]
+        {
+            let runtime = wasm::Runtime::load(&config)?;
+            Ok(Self { config, runtime })
+        }
+
+        #[cfg(not(feature = "plugins"))]
+        {
+            Err(ClaudeUtilsError::Config(format!(
+                "Plugin {}: claude-utils was built without the `plugins` feature",
+                config.name
+            )))
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    pub fn tool_name(&self) -> String {
+        format!("plugin.{}", self.config.name)
+    }
+
+    pub fn description(&self) -> String {
+        self.config
+            .description
+            .clone()
+            .unwrap_or_else(|| format!("Run the {} plugin on the clipboard", self.config.name))
+    }
+
+    pub fn call(&self, input: &PluginInput) -> Result<PluginOutput> {
+        let output = self.run(&serde_json::to_vec(input)?)?;
+        let output: PluginOutput = serde_json::from_slice(&output).map_err(|e| {
+            ClaudeUtilsError::Plugin(format!("{}: invalid output: {e}", self.config.name))
+        })?;
+
+        match &output.content {
+            None
+            | Some(ClipboardContent::Text { .. })
+            | Some(ClipboardContent::Markdown { .. })
+            | Some(ClipboardContent::Html { .. }) => Ok(output),
+            Some(_) => Err(ClaudeUtilsError::Plugin(format!(
+                "{}: only text, Markdown or HTML content can be returned",
+                self.config.name
+            ))),
+        }
+    }
+
+    #[cfg(feature = "plugins")]
+    fn run(&self, input: &[u8]) -> Result<Vec<u8>> {
+        self.runtime.call(self.config.fuel, input)
+    }
+
+    #[cfg(not(feature = "plugins"))]
+    fn run(&self, _input: &[u8]) -> Result<Vec<u8>> {
+        Err(ClaudeUtilsError::Plugin(format!(
+            "{}: claude-utils was built without the `plugins` feature",
+            self.config.name
+        )))
+    }
+}
+
+/// The loaded plugins. Clones share them.
+#[derive(Clone, Default)]
+pub struct PluginHost {
+    plugins: Arc<Vec<Plugin>>,
+}
+
+impl PluginHost {
+    /// Compiles every configured plugin; any that fails to load is an error.
+    pub fn load(configs: Vec<PluginConfig>) -> Result<Self> {
+        let plugins = configs
+            .into_iter()
+            .map(Plugin::load)
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            plugins: Arc::new(plugins),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    /// The plugins offered as MCP tools.
+    pub fn tools(&self) -> impl Iterator<Item = &Plugin> {
+        self.plugins.iter().filter(|plugin| plugin.config.tool)
+    }
+
+    pub fn tool(&self, tool_name: &str) -> Option<&Plugin> {
+        let name = tool_name.strip_prefix("plugin.")?;
+        self.tools().find(|plugin| plugin.name() == name)
+    }
+
+    /// Runs the `tool_name` plugin on `input`.
+    pub async fn call_tool(&self, tool_name: &str, input: PluginInput) -> Result<PluginOutput> {
+        let host = self.clone();
+        let tool_name = tool_name.to_string();
+
+        blocking(move || match host.tool(&tool_name) {
+            Some(plugin) => plugin.call(&input),
+            None => Err(ClaudeUtilsError::Plugin(format!(
+                "Unknown tool: {tool_name}"
+            ))),
+        })
+        .await
+    }
+
+    /// Runs the watch plugins over `data` in order. Each sees what the one
+    /// before returned, and the first veto ends the run.
+    pub async fn apply(&self, mut data: ClipboardData) -> Result<(ClipboardData, Verdict)> {
+        let host = self.clone();
+
+        blocking(move || {
+            let verdict = host.apply_to(&mut data)?;
+            Ok((data, verdict))
+        })
+        .await
+    }
+
+    fn apply_to(&self, data: &mut ClipboardData) -> Result<Verdict> {
+        let mut verdict = Verdict::Pass;
+
+        for plugin in self.plugins.iter().filter(|plugin| plugin.config.watch) {
+            let output = plugin.call(&PluginInput {
+                stage: PluginStage::Watch,
+                data: data.clone(),
+                arguments: Value::Null,
+            })?;
+
+            if let Some(reason) = output.veto {
+                return Ok(Verdict::Veto {
+                    plugin: plugin.name().to_string(),
+                    reason,
+                });
+            }
+            if let Some(content) = output.content {
+                data.content = content;
+                verdict = Verdict::Rewritten;
+            }
+            data.metadata.annotations.extend(output.metadata);
+        }
+
+        Ok(verdict)
+    }
+}
+
+/// Runs `f` on a blocking thread: a plugin may spend its whole fuel budget,
+/// and the async runtime must not wait for it.
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| ClaudeUtilsError::Plugin(format!("Plugin task failed: {e}")))?
+}
+
+impl std::fmt::Debug for PluginHost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.plugins.iter().map(|plugin| plugin.name()))
+            .finish()
+    }
+}
@@ -0,0 +1,78 @@
+use wasmtime::{Engine, Instance, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use super::PluginConfig;
+use crate::{ClaudeUtilsError, Result};
+
+/// Linear memory a plugin may grow to.
+const MAX_MEMORY: usize = 64 * 1024 * 1024;
+
+pub(super) struct Runtime {
+    name: String,
+    engine: Engine,
+    module: Module,
+}
+
+impl Runtime {
+    pub(super) fn load(config: &PluginConfig) -> Result<Self> {
+        let fail = |e: wasmtime::Error| {
+            ClaudeUtilsError::Plugin(format!(
+                "{}: cannot load {}: {e}",
+                config.name,
+                config.path.display()
+            ))
+        };
+
+        let mut engine_config = wasmtime::Config::new();
+        engine_config.consume_fuel(true);
+        let engine = Engine::new(&engine_config).map_err(fail)?;
+        let module = Module::from_file(&engine, &config.path).map_err(fail)?;
+
+        Ok(Self {
+            name: config.name.clone(),
+            engine,
+            module,
+        })
+    }
+
+    /// Runs `process` on `input` in a fresh instance, so no state survives
+    /// between calls.
+    pub(super) fn call(&self, fuel: u64, input: &[u8]) -> Result<Vec<u8>> {
+        let fail = |e: wasmtime::Error| ClaudeUtilsError::Plugin(format!("{}: {e}", self.name));
+
+        let limits = StoreLimitsBuilder::new().memory_size(MAX_MEMORY).build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits: &mut StoreLimits| limits);
+        store.set_fuel(fuel).map_err(fail)?;
+
+        // No imports: a plugin sees nothing but its input
+        let instance = Instance::new(&mut store, &self.module, &[]).map_err(fail)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| fail(wasmtime::Error::msg("no exported memory")))?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, "alloc")
+            .map_err(fail)?;
+        let process = instance
+            .get_typed_func::<(i32, i32), i64>(&mut store, "process")
+            .map_err(fail)?;
+
+        let len = i32::try_from(input.len())
+            .map_err(|_| fail(wasmtime::Error::msg("input too large")))?;
+        let ptr = alloc.call(&mut store, len).map_err(fail)?;
+        memory
+            .write(&mut store, ptr as u32 as usize, input)
+            .map_err(|e| fail(e.into()))?;
+
+        let packed = process.call(&mut store, (ptr, len)).map_err(fail)? as u64;
+        let (offset, len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        if offset.saturating_add(len) > memory.data_size(&store) {
+            return Err(fail(wasmtime::Error::msg("output outside of memory")));
+        }
+
+        let mut output = vec![0; len];
+        memory
+            .read(&store, offset, &mut output)
+            .map_err(|e| fail(e.into()))?;
+        Ok(output)
+    }
+}
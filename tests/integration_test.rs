@@ -1,13 +1,107 @@
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
 use claude_utils::{
-    clipboard::{ClipboardContent, ClipboardManager},
+    clipboard::{
+        delivery::{Delivery, LinkTemplate},
+        events::{EventBus, LagPolicy, Subscription},
+        filter::{FilterConfig, WatchFilter},
+        processor::{ClipboardProcessor, ProcessorConfig},
+        rules::{RuleConfig, RuleSet},
+        watcher::{ClipboardEvent, ClipboardWatcher, WatchMode},
+        ClipboardBackend, ClipboardContent, ClipboardManager, MemoryClipboard, Origin, OwnerChange,
+        RawImage, Selection,
+    },
+    config::Config,
     file_manager::{FileManager, FileManagerConfig},
+    mcp::{
+        auth::{AuthConfig, AuthManager},
+        server::McpServer,
+    },
 };
+use serde_json::{json, Value};
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::sleep;
+use tower::ServiceExt;
+
+/// A watcher with one subscriber that sees every event.
+fn watcher_with_subscriber(
+    clipboard: Arc<ClipboardManager>,
+    poll_interval: Duration,
+) -> (ClipboardWatcher, Subscription) {
+    let bus = EventBus::new();
+    let events = bus.subscribe("test", 100, LagPolicy::Block);
+    (ClipboardWatcher::new(clipboard, poll_interval, bus), events)
+}
+
+async fn staging_file_manager(dir: &tempfile::TempDir) -> Arc<FileManager> {
+    let config = FileManagerConfig {
+        staging_dir: dir.path().join("staging"),
+        ..Default::default()
+    };
+
+    Arc::new(
+        FileManager::new(config)
+            .await
+            .expect("Failed to create file manager"),
+    )
+}
+
+async fn test_server(clipboard: Arc<ClipboardManager>, dir: &tempfile::TempDir) -> McpServer {
+    let auth_manager = AuthManager::new(AuthConfig {
+        require_auth: false,
+        ..Default::default()
+    })
+    .await
+    .expect("Failed to create auth manager");
+
+    McpServer::new(
+        clipboard,
+        staging_file_manager(dir).await,
+        auth_manager,
+        0,
+        "127.0.0.1".to_string(),
+    )
+    .await
+    .expect("Failed to create server")
+    .with_writes(true)
+}
+
+async fn post_json(
+    app: &Router,
+    uri: &str,
+    session: Option<&str>,
+    body: Value,
+) -> (StatusCode, Value) {
+    let mut request = Request::post(uri)
+        .header("content-type", "application/json")
+        .header("accept", "application/json, text/event-stream");
+    if let Some(session) = session {
+        request = request.header("mcp-session-id", session);
+    }
+
+    let response = app
+        .clone()
+        .oneshot(request.body(Body::from(body.to_string())).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
 
 #[tokio::test]
 async fn test_clipboard_text_roundtrip() {
-    let clipboard = ClipboardManager::new().expect("Failed to create clipboard manager");
+    let clipboard = ClipboardManager::with_backend(MemoryClipboard::new());
 
     // Set text
     let test_text = "Hello, Claude-Utils!";
@@ -34,6 +128,1727 @@ async fn test_clipboard_text_roundtrip() {
     }
 }
 
+#[test]
+#[ignore = "requires a display server"]
+fn test_system_clipboard_offers_path_and_image() {
+    let clipboard = ClipboardManager::new().expect("Failed to create clipboard manager");
+    let png = claude_utils::clipboard::backend::encode_png(&RawImage {
+        width: 1,
+        height: 1,
+        bytes: vec![0, 128, 255, 255],
+    })
+    .unwrap();
+
+    clipboard
+        .set_formats(
+            Selection::Clipboard,
+            &[
+                (
+                    "text/plain;charset=utf-8".to_string(),
+                    b"/tmp/shot.png".to_vec(),
+                ),
+                ("image/png".to_string(), png.clone()),
+            ],
+        )
+        .unwrap();
+
+    let formats = clipboard.list_formats(Selection::Clipboard).unwrap();
+    let offered: Vec<&str> = formats.iter().map(|format| format.mime.as_str()).collect();
+    assert!(offered
+        .iter()
+        .any(|mime| *mime == "UTF8_STRING" || *mime == "text/plain"));
+    assert!(offered.contains(&"image/png"));
+
+    // Sizes come from the display server, without reading the targets
+    let image = formats.iter().find(|format| format.mime == "image/png");
+    assert_eq!(image.unwrap().size, Some(png.len()));
+
+    assert_eq!(
+        clipboard
+            .get_format(Selection::Clipboard, "image/png")
+            .unwrap(),
+        png
+    );
+}
+
+#[tokio::test]
+#[ignore = "requires a display server"]
+async fn test_system_clipboard_text_roundtrip() {
+    let clipboard = ClipboardManager::new().expect("Failed to create clipboard manager");
+
+    let content = ClipboardContent::Text {
+        data: "Hello from the system clipboard".to_string(),
+        truncated: None,
+    };
+    clipboard
+        .set_content(&content)
+        .expect("Failed to set clipboard");
+
+    sleep(Duration::from_millis(100)).await;
+
+    match clipboard
+        .get_content()
+        .expect("Failed to get clipboard")
+        .content
+    {
+        ClipboardContent::Text { data, .. } => {
+            assert_eq!(data, "Hello from the system clipboard");
+        }
+        _ => panic!("Expected text content"),
+    }
+}
+
+#[tokio::test]
+async fn test_memory_clipboard_image() {
+    let memory = MemoryClipboard::new();
+    let clipboard = ClipboardManager::with_backend(memory.clone());
+
+    let mut other_app = memory;
+    other_app
+        .set_image(
+            Selection::Clipboard,
+            &RawImage {
+                width: 2,
+                height: 1,
+                bytes: vec![255, 0, 0, 255, 0, 0, 255, 255],
+            },
+        )
+        .unwrap();
+
+    match clipboard.get_content().unwrap().content {
+        ClipboardContent::ImagePng {
+            data,
+            width,
+            height,
+            ..
+        } => {
+            assert!(data.is_some());
+            assert_eq!((width, height), (2, 1));
+        }
+        _ => panic!("Expected image content"),
+    }
+
+    let png = clipboard.get_raw_image().unwrap();
+    assert_eq!(&png[1..4], b"PNG");
+}
+
+#[tokio::test]
+async fn test_watcher_reports_new_content() {
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+    let (watcher, mut events) = watcher_with_subscriber(clipboard, Duration::from_millis(10));
+    tokio::spawn(watcher.start_watching());
+
+    let mut other_app = memory;
+    other_app
+        .set_text(Selection::Clipboard, "copied elsewhere")
+        .unwrap();
+
+    let event = tokio::time::timeout(Duration::from_secs(2), events.recv())
+        .await
+        .expect("Timed out waiting for clipboard event")
+        .expect("Watcher stopped");
+
+    match event.content.content {
+        ClipboardContent::Text { data, .. } => assert_eq!(data, "copied elsewhere"),
+        _ => panic!("Expected text content"),
+    }
+    assert_eq!(event.mime_types, vec!["text/plain".to_string()]);
+}
+
+#[tokio::test]
+async fn test_watcher_ignores_own_writes() {
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+    let (watcher, mut events) =
+        watcher_with_subscriber(clipboard.clone(), Duration::from_millis(10));
+    let watcher = watcher.with_idle_interval(Duration::from_secs(1));
+    let control = watcher.control();
+    tokio::spawn(watcher.start_watching());
+
+    let path = ClipboardContent::Text {
+        data: "/home/me/Desktop/claude-paste.png".to_string(),
+        truncated: None,
+    };
+    clipboard.set_content(&path).unwrap();
+    assert_eq!(
+        clipboard.get_content().unwrap().metadata.origin,
+        Origin::Daemon
+    );
+
+    // Written to an empty clipboard, it is still only seen once, and
+    // polling backs off after it
+    sleep(Duration::from_millis(300)).await;
+    assert!(control.status().poll_interval_ms.unwrap() > 10);
+
+    let mut other_app = memory;
+    other_app
+        .set_text(Selection::Clipboard, "copied by the user")
+        .unwrap();
+
+    // The first event is the user's copy, not our write before it
+    let event = tokio::time::timeout(Duration::from_secs(2), events.recv())
+        .await
+        .expect("Timed out waiting for clipboard event")
+        .expect("Watcher stopped");
+
+    match event.content.content {
+        ClipboardContent::Text { data, .. } => assert_eq!(data, "copied by the user"),
+        _ => panic!("Expected text content"),
+    }
+    assert_eq!(event.content.metadata.origin, Origin::User);
+    assert_eq!(
+        clipboard.get_content().unwrap().metadata.origin,
+        Origin::User
+    );
+}
+
+#[tokio::test]
+async fn test_watcher_skips_filtered_content() {
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+    let filter = WatchFilter::new(FilterConfig {
+        ignore_types: vec!["image/*".to_string()],
+        max_size: Some(1000),
+        ignore_patterns: vec!["^sk-[a-z]+$".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+    let (watcher, mut events) =
+        watcher_with_subscriber(clipboard.clone(), Duration::from_millis(10));
+    tokio::spawn(watcher.with_filter(filter).start_watching());
+
+    let mut other_app = memory;
+    // What KeePassXC offers when copying a password
+    other_app
+        .set_formats(
+            Selection::Clipboard,
+            &[
+                ("text/plain".to_string(), b"hunter2".to_vec()),
+                ("x-kde-passwordManagerHint".to_string(), b"secret".to_vec()),
+            ],
+        )
+        .unwrap();
+    sleep(Duration::from_millis(50)).await;
+
+    other_app
+        .set_text(Selection::Clipboard, "sk-livekey")
+        .unwrap();
+    sleep(Duration::from_millis(50)).await;
+
+    other_app
+        .set_image(
+            Selection::Clipboard,
+            &RawImage {
+                width: 1,
+                height: 1,
+                bytes: vec![0, 0, 0, 255],
+            },
+        )
+        .unwrap();
+    sleep(Duration::from_millis(50)).await;
+
+    other_app
+        .set_text(Selection::Clipboard, &"x".repeat(2000))
+        .unwrap();
+    sleep(Duration::from_millis(50)).await;
+
+    assert!(events.try_recv().is_none());
+
+    other_app
+        .set_text(Selection::Clipboard, "ordinary text")
+        .unwrap();
+    let event = tokio::time::timeout(Duration::from_secs(2), events.recv())
+        .await
+        .expect("Timed out waiting for clipboard event")
+        .expect("Watcher stopped");
+
+    assert_eq!(event.snapshot.text().as_deref(), Some("ordinary text"));
+    // Withheld copies do not use up sequence numbers
+    assert_eq!(event.sequence, 1);
+}
+
+#[tokio::test]
+async fn test_watcher_pause_resume_and_backoff() {
+    let dir = tempfile::tempdir().unwrap();
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+    let (watcher, mut events) =
+        watcher_with_subscriber(clipboard.clone(), Duration::from_millis(10));
+    let watcher = watcher.with_idle_interval(Duration::from_millis(40));
+    let control = watcher.control();
+    let app = test_server(clipboard, &dir)
+        .await
+        .with_watch_control(control.clone())
+        .router();
+    tokio::spawn(watcher.start_watching());
+
+    // Nothing changes, so polling slows down to the idle interval
+    sleep(Duration::from_millis(300)).await;
+    let status = control.status();
+    assert_eq!(status.mode, WatchMode::Polling);
+    assert_eq!(status.poll_interval_ms, Some(40));
+
+    let (_, response) = post_json(
+        &app,
+        "/rpc",
+        None,
+        json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call",
+               "params": {"name": "watch.pause", "arguments": {}}}),
+    )
+    .await;
+    let status: Value =
+        serde_json::from_str(response["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(status["paused"], true);
+
+    // Copies while paused are never reported, not even after resuming
+    let mut other_app = memory;
+    other_app
+        .set_text(Selection::Clipboard, "copied during a screen share")
+        .unwrap();
+    sleep(Duration::from_millis(100)).await;
+    control.resume();
+    sleep(Duration::from_millis(100)).await;
+    assert!(events.try_recv().is_none());
+
+    other_app
+        .set_text(Selection::Clipboard, "copied afterwards")
+        .unwrap();
+    let event = tokio::time::timeout(Duration::from_secs(2), events.recv())
+        .await
+        .expect("Timed out waiting for clipboard event")
+        .expect("Watcher stopped");
+    assert_eq!(event.snapshot.text().as_deref(), Some("copied afterwards"));
+}
+
+#[tokio::test]
+async fn test_on_demand_watcher_waits_for_a_client() {
+    let dir = tempfile::tempdir().unwrap();
+    let memory = MemoryClipboard::new();
+    let mut other_app = memory.clone();
+    other_app
+        .set_text(Selection::Clipboard, "copied before anyone asked")
+        .unwrap();
+
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory));
+    let (watcher, mut events) =
+        watcher_with_subscriber(clipboard.clone(), Duration::from_millis(10));
+    let watcher = watcher.on_demand();
+    let control = watcher.control();
+    let app = test_server(clipboard, &dir)
+        .await
+        .with_watch_control(control.clone())
+        .router();
+    tokio::spawn(watcher.start_watching());
+
+    sleep(Duration::from_millis(100)).await;
+    assert_eq!(control.status().mode, WatchMode::Idle);
+    assert!(events.try_recv().is_none());
+
+    // Waiting for a change starts the watcher, which takes what is already
+    // there as seen
+    let (_, response) = post_json(
+        &app,
+        "/rpc",
+        None,
+        json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call",
+               "params": {"name": "clipboard.wait", "arguments": {"timeout_ms": 100}}}),
+    )
+    .await;
+    let result: Value =
+        serde_json::from_str(response["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(result["changed"], false);
+    assert_eq!(control.status().mode, WatchMode::Polling);
+
+    other_app
+        .set_text(Selection::Clipboard, "copied afterwards")
+        .unwrap();
+    let event = tokio::time::timeout(Duration::from_secs(2), events.recv())
+        .await
+        .expect("Timed out waiting for clipboard event")
+        .expect("Watcher stopped");
+    assert_eq!(event.snapshot.text().as_deref(), Some("copied afterwards"));
+}
+
+#[tokio::test]
+async fn test_watcher_tells_apart_large_images_of_equal_size() {
+    // Noise does not compress, so both PNGs are well past MAX_INLINE_SIZE
+    let mut seed = 1u32;
+    let noise: Vec<u8> = (0..200 * 200 * 4)
+        .map(|_| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) as u8
+        })
+        .collect();
+    let mut reversed = noise.clone();
+    reversed.reverse();
+
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+    let (watcher, mut events) = watcher_with_subscriber(clipboard, Duration::from_millis(10));
+    tokio::spawn(watcher.start_watching());
+
+    let mut other_app = memory;
+    for bytes in [noise, reversed] {
+        other_app
+            .set_image(
+                Selection::Clipboard,
+                &RawImage {
+                    width: 200,
+                    height: 200,
+                    bytes,
+                },
+            )
+            .unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(2), events.recv())
+            .await
+            .expect("Screenshot was not detected")
+            .expect("Watcher stopped");
+
+        match event.content.content {
+            ClipboardContent::ImagePng { data, width, .. } => {
+                assert!(data.is_none());
+                assert_eq!(width, 200);
+            }
+            _ => panic!("Expected image content"),
+        }
+    }
+}
+
+/// Run inside a headless compositor, e.g.
+/// `WLR_BACKENDS=headless WLR_LIBINPUT_NO_DEVICES=1 sway -c /dev/null &`
+/// then `WAYLAND_DISPLAY=wayland-1 cargo test -- --ignored wayland`.
+#[cfg(target_os = "linux")]
+#[tokio::test]
+#[ignore = "requires a Wayland compositor with data-control"]
+async fn test_wayland_watcher_reports_offered_types() {
+    use claude_utils::clipboard::WaylandClipboard;
+
+    let clipboard = Arc::new(ClipboardManager::with_backend(
+        WaylandClipboard::new().expect("WAYLAND_DISPLAY not set"),
+    ));
+    // Far longer than the test, so only a data-control event can deliver it
+    let (watcher, mut events) = watcher_with_subscriber(clipboard, Duration::from_secs(600));
+    tokio::spawn(watcher.start_watching());
+    sleep(Duration::from_millis(200)).await;
+
+    let mut other_app = WaylandClipboard::new().unwrap();
+    other_app
+        .set_formats(
+            Selection::Clipboard,
+            &[
+                ("text/html".to_string(), b"<b>from sway</b>".to_vec()),
+                ("text/plain".to_string(), b"from sway".to_vec()),
+            ],
+        )
+        .unwrap();
+
+    let event = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let event = events.recv().await.expect("Watcher stopped");
+            if event.mime_types.iter().any(|mime| mime == "text/html") {
+                return event;
+            }
+        }
+    })
+    .await
+    .expect("Timed out waiting for the data-control event");
+
+    assert!(event
+        .mime_types
+        .iter()
+        .any(|mime| mime.starts_with("text/plain")));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+#[ignore = "requires a Wayland compositor with data-control"]
+fn test_wayland_list_formats_reports_sizes() {
+    use claude_utils::clipboard::WaylandClipboard;
+
+    let clipboard =
+        ClipboardManager::with_backend(WaylandClipboard::new().expect("WAYLAND_DISPLAY not set"));
+    let html = "<b>sized</b>".repeat(10_000);
+    clipboard
+        .set_formats(
+            Selection::Clipboard,
+            &[
+                ("text/html".to_string(), html.clone().into_bytes()),
+                ("text/plain".to_string(), b"sized".to_vec()),
+            ],
+        )
+        .unwrap();
+
+    let formats = clipboard.list_formats(Selection::Clipboard).unwrap();
+    let size = |mime: &str| {
+        formats
+            .iter()
+            .find(|format| format.mime == mime)
+            .and_then(|format| format.size)
+    };
+    assert_eq!(size("text/html"), Some(html.len()));
+    assert_eq!(size("text/plain"), Some(5));
+}
+
+/// Memory clipboard that, like X11 with XFixes, announces new owners.
+struct NotifyingClipboard {
+    memory: MemoryClipboard,
+    changes: Option<tokio::sync::mpsc::UnboundedReceiver<OwnerChange>>,
+}
+
+impl ClipboardBackend for NotifyingClipboard {
+    fn name(&self) -> &'static str {
+        "notifying"
+    }
+
+    fn get_text(&mut self, selection: Selection) -> claude_utils::Result<String> {
+        self.memory.get_text(selection)
+    }
+
+    fn set_text(&mut self, selection: Selection, text: &str) -> claude_utils::Result<()> {
+        self.memory.set_text(selection, text)
+    }
+
+    fn get_image(&mut self, selection: Selection) -> claude_utils::Result<RawImage> {
+        self.memory.get_image(selection)
+    }
+
+    fn set_image(&mut self, selection: Selection, image: &RawImage) -> claude_utils::Result<()> {
+        self.memory.set_image(selection, image)
+    }
+
+    fn formats(&mut self, selection: Selection) -> claude_utils::Result<Vec<String>> {
+        self.memory.formats(selection)
+    }
+
+    fn get_format(&mut self, selection: Selection, mime: &str) -> claude_utils::Result<Vec<u8>> {
+        self.memory.get_format(selection, mime)
+    }
+
+    fn set_formats(
+        &mut self,
+        selection: Selection,
+        formats: &[(String, Vec<u8>)],
+    ) -> claude_utils::Result<()> {
+        self.memory.set_formats(selection, formats)
+    }
+
+    fn owner_changes(
+        &mut self,
+        _selections: &[Selection],
+    ) -> Option<tokio::sync::mpsc::UnboundedReceiver<OwnerChange>> {
+        self.changes.take()
+    }
+}
+
+#[tokio::test]
+async fn test_watcher_reads_only_on_owner_change() {
+    let memory = MemoryClipboard::new();
+    let (owner_changed, changes) = tokio::sync::mpsc::unbounded_channel();
+    let clipboard = Arc::new(ClipboardManager::with_backend(NotifyingClipboard {
+        memory: memory.clone(),
+        changes: Some(changes),
+    }));
+    let (watcher, mut events) = watcher_with_subscriber(clipboard, Duration::from_millis(10));
+    tokio::spawn(watcher.start_watching());
+
+    // Without a notification the watcher must not poll its way to this
+    sleep(Duration::from_millis(50)).await;
+    let mut other_app = memory;
+    other_app
+        .set_text(Selection::Clipboard, "announced")
+        .unwrap();
+    sleep(Duration::from_millis(100)).await;
+    assert!(events.try_recv().is_none());
+
+    owner_changed
+        .send(OwnerChange {
+            selection: Selection::Clipboard,
+            mime_types: Some(vec![
+                "text/plain".to_string(),
+                "application/x-announced".to_string(),
+            ]),
+        })
+        .unwrap();
+
+    let event = tokio::time::timeout(Duration::from_secs(2), events.recv())
+        .await
+        .expect("Timed out waiting for clipboard event")
+        .expect("Watcher stopped");
+
+    match event.content.content {
+        ClipboardContent::Text { data, .. } => assert_eq!(data, "announced"),
+        _ => panic!("Expected text content"),
+    }
+    // The types come from the announcement, not a later look at the owner
+    assert_eq!(
+        event.mime_types,
+        vec!["text/plain", "application/x-announced"]
+    );
+}
+
+#[tokio::test]
+async fn test_processor_stages_copied_image() {
+    let dir = tempfile::tempdir().unwrap();
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+    let (watcher, events) = watcher_with_subscriber(clipboard.clone(), Duration::from_millis(10));
+
+    let config = ProcessorConfig {
+        symlink_dir: dir.path().to_path_buf(),
+        enable_notifications: false,
+        ..Default::default()
+    };
+    let output = EventBus::new();
+    let mut processed = output.subscribe("test", 4, LagPolicy::Block);
+    let processor =
+        ClipboardProcessor::new(config, staging_file_manager(&dir).await, clipboard.clone())
+            .with_output(output);
+
+    tokio::spawn(watcher.start_watching());
+    tokio::spawn(processor.start_processing(events));
+
+    let mut other_app = memory.clone();
+    other_app
+        .set_image(
+            Selection::Clipboard,
+            &RawImage {
+                width: 1,
+                height: 1,
+                bytes: vec![0, 128, 255, 255],
+            },
+        )
+        .unwrap();
+
+    // Passed on once processed, with the paths filled in
+    let event = tokio::time::timeout(Duration::from_secs(5), processed.recv())
+        .await
+        .expect("Timed out waiting for the processed event")
+        .expect("Processor stopped");
+    assert!(event.staged_path.is_some());
+    assert!(event.symlink_path.is_some());
+
+    // The processor replaces the image with a path to the staged copy
+    let mut reader = memory;
+    let path = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(text) = reader.get_text(Selection::Clipboard) {
+                return text;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("Processor never rewrote the clipboard");
+
+    assert!(path.starts_with(dir.path().to_str().unwrap()));
+    assert!(path.ends_with(".png"));
+    let staged = image::open(&path).expect("Symlink should point at the staged PNG");
+    assert_eq!((staged.width(), staged.height()), (1, 1));
+
+    // Image apps can still paste the picture itself
+    if cfg!(target_os = "linux") {
+        let image = reader.get_image(Selection::Clipboard).unwrap();
+        assert_eq!(image.bytes, vec![0, 128, 255, 255]);
+    }
+}
+
+#[test]
+fn test_snapshot_fingerprint_matches_clipboard() {
+    let clipboard = ClipboardManager::with_backend(MemoryClipboard::new());
+    let contents = [
+        ("text/plain", b"plain".to_vec()),
+        ("text/html", b"<b>rich</b>".to_vec()),
+        ("text/uri-list", b"file:///tmp/a.txt\r\n".to_vec()),
+    ];
+
+    for (mime, bytes) in contents {
+        clipboard
+            .set_formats(Selection::Clipboard, &[(mime.to_string(), bytes)])
+            .unwrap();
+        let snapshot = clipboard.capture(Selection::Clipboard).unwrap();
+        let fingerprint = clipboard.fingerprint(Selection::Clipboard).unwrap();
+
+        assert_eq!(snapshot.fingerprint(), fingerprint, "{mime}");
+        assert!(clipboard
+            .capture_if_changed(Selection::Clipboard, fingerprint)
+            .unwrap()
+            .is_none());
+    }
+
+    let image = RawImage {
+        width: 1,
+        height: 1,
+        bytes: vec![0, 128, 255, 255],
+    };
+    let mut backend = MemoryClipboard::new();
+    backend.set_image(Selection::Clipboard, &image).unwrap();
+    let clipboard = ClipboardManager::with_backend(backend);
+    let snapshot = clipboard.capture(Selection::Clipboard).unwrap();
+    assert_eq!(
+        snapshot.fingerprint(),
+        clipboard.fingerprint(Selection::Clipboard).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_processor_works_from_the_snapshot() {
+    let dir = tempfile::tempdir().unwrap();
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+    let mut other_app = memory;
+
+    let config = ProcessorConfig {
+        symlink_dir: dir.path().to_path_buf(),
+        enable_notifications: false,
+        enable_dual_format: false,
+        ..Default::default()
+    };
+    let processor =
+        ClipboardProcessor::new(config, staging_file_manager(&dir).await, clipboard.clone());
+
+    let bus = EventBus::new();
+    let subscription = bus.subscribe("processor", 4, LagPolicy::Block);
+    let capture = |selection| {
+        let snapshot = clipboard.capture(selection).unwrap();
+        ClipboardEvent {
+            sequence: 1,
+            content: clipboard.describe(&snapshot).unwrap(),
+            snapshot,
+            mime_types: Vec::new(),
+            staged_path: None,
+            symlink_path: None,
+        }
+    };
+
+    other_app
+        .set_image(
+            Selection::Clipboard,
+            &RawImage {
+                width: 2,
+                height: 1,
+                bytes: vec![0, 128, 255, 255, 255, 128, 0, 255],
+            },
+        )
+        .unwrap();
+    let image_event = capture(Selection::Clipboard);
+
+    let long_text = "x".repeat(100_000);
+    other_app
+        .set_text(Selection::Clipboard, &long_text)
+        .unwrap();
+    let text_event = capture(Selection::Clipboard);
+
+    // Something else is copied before the processor gets to the events
+    other_app
+        .set_text(Selection::Clipboard, "copied later")
+        .unwrap();
+
+    bus.publish(image_event).await;
+    bus.publish(text_event).await;
+    drop(bus);
+    processor.start_processing(subscription).await;
+
+    let staged = image::open(dir.path().join("claude-paste.png")).unwrap();
+    assert_eq!((staged.width(), staged.height()), (2, 1));
+
+    // Staged in full, not cut down to the inline size
+    let staged = std::fs::read_to_string(dir.path().join("claude-paste.txt")).unwrap();
+    assert_eq!(staged, long_text);
+}
+
+#[tokio::test]
+async fn test_processor_leaves_images_without_dual_format() {
+    let dir = tempfile::tempdir().unwrap();
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+    let mut other_app = memory;
+
+    let config = ProcessorConfig {
+        symlink_dir: dir.path().to_path_buf(),
+        enable_notifications: false,
+        enable_dual_format: false,
+        ..Default::default()
+    };
+    let processor =
+        ClipboardProcessor::new(config, staging_file_manager(&dir).await, clipboard.clone());
+
+    let image = RawImage {
+        width: 1,
+        height: 1,
+        bytes: vec![0, 128, 255, 255],
+    };
+    other_app.set_image(Selection::Clipboard, &image).unwrap();
+
+    let bus = EventBus::new();
+    let subscription = bus.subscribe("processor", 4, LagPolicy::Block);
+    let snapshot = clipboard.capture(Selection::Clipboard).unwrap();
+    bus.publish(ClipboardEvent {
+        sequence: 1,
+        content: clipboard.describe(&snapshot).unwrap(),
+        snapshot,
+        mime_types: Vec::new(),
+        staged_path: None,
+        symlink_path: None,
+    })
+    .await;
+    drop(bus);
+    processor.start_processing(subscription).await;
+
+    // Still staged and linked, but the clipboard keeps the image
+    assert!(dir.path().join("claude-paste.png").exists());
+    assert_eq!(other_app.get_image(Selection::Clipboard).unwrap(), image);
+    assert!(other_app.get_text(Selection::Clipboard).is_err());
+}
+
+#[tokio::test]
+async fn test_processor_follows_configured_rules() {
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("config.toml");
+    std::fs::write(
+        &config_path,
+        r#"
+[[watch.rules]]
+name = "links"
+match = { pattern = "^https?://" }
+actions = ["leave"]
+
+[[watch.rules]]
+name = "rich text as markdown"
+match = { types = ["text/html"] }
+actions = ["convert", "stage", "symlink"]
+convert_to = "markdown"
+"#,
+    )
+    .unwrap();
+    let config = Config::load(&config_path).unwrap();
+
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+    let mut other_app = memory;
+
+    let processor_config = ProcessorConfig {
+        symlink_dir: dir.path().to_path_buf(),
+        enable_notifications: false,
+        rules: RuleSet::new(config.watch.rules.unwrap()).unwrap(),
+        ..Default::default()
+    };
+    let processor = ClipboardProcessor::new(
+        processor_config,
+        staging_file_manager(&dir).await,
+        clipboard.clone(),
+    );
+
+    let bus = EventBus::new();
+    let subscription = bus.subscribe("processor", 4, LagPolicy::Block);
+    let html = "<h1>Notes</h1><p>See <a href=\"https://example.com\">this</a></p>";
+    other_app
+        .set_formats(
+            Selection::Clipboard,
+            &[("text/html".to_string(), html.as_bytes().to_vec())],
+        )
+        .unwrap();
+    let snapshot = clipboard.capture(Selection::Clipboard).unwrap();
+    bus.publish(ClipboardEvent {
+        sequence: 1,
+        content: clipboard.describe(&snapshot).unwrap(),
+        snapshot,
+        mime_types: Vec::new(),
+        staged_path: None,
+        symlink_path: None,
+    })
+    .await;
+    drop(bus);
+    processor.start_processing(subscription).await;
+
+    let staged = std::fs::read_to_string(dir.path().join("claude-paste.md")).unwrap();
+    assert!(staged.starts_with("Notes"));
+    assert!(staged.contains("[this](https://example.com)"));
+
+    // Without `rewrite` the copied content stays on the clipboard
+    assert_eq!(
+        other_app
+            .get_format(Selection::Clipboard, "text/html")
+            .unwrap(),
+        html.as_bytes()
+    );
+
+    // `leave` with any other action, or `convert` without staging, is a configuration error
+    for actions in [
+        r#"actions = ["leave", "rewrite"]"#,
+        r#"actions = ["leave", "notify"]"#,
+        r#"actions = ["convert", "notify"]
+convert_to = "png""#,
+    ] {
+        let conflicting: RuleConfig = toml::from_str(actions).unwrap();
+        assert!(RuleSet::new(vec![conflicting]).is_err(), "{actions}");
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_processor_runs_hooks() {
+    let dir = tempfile::tempdir().unwrap();
+    let event_path = dir.path().join("event.json");
+    let config: Config = toml::from_str(&format!(
+        r#"
+[[watch.rules]]
+name = "notes"
+match = {{ types = ["text/plain"] }}
+actions = ["stage", "rewrite"]
+
+[[watch.hooks]]
+name = "slow background"
+command = ["sleep", "3"]
+
+[[watch.hooks]]
+name = "upload"
+command = ["sh", "-c", "cat > \"$1\"; echo \"https://bucket.example/$CLAUDE_UTILS_SEQUENCE/$(basename \"$2\")\"", "hook", "{}"]
+replace_clipboard = true
+
+[[watch.hooks]]
+name = "stuck"
+command = ["sleep", "5"]
+timeout_ms = 100
+replace_clipboard = true
+"#,
+        event_path.display()
+    ))
+    .unwrap();
+
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+    let mut other_app = memory;
+
+    let processor_config = ProcessorConfig {
+        symlink_dir: dir.path().to_path_buf(),
+        enable_notifications: false,
+        rules: RuleSet::new(config.watch.rules.unwrap()).unwrap(),
+        hooks: config.watch.hooks,
+        max_concurrent_hooks: 1,
+        ..Default::default()
+    };
+    let processor = ClipboardProcessor::new(
+        processor_config,
+        staging_file_manager(&dir).await,
+        clipboard.clone(),
+    );
+
+    let bus = EventBus::new();
+    let subscription = bus.subscribe("processor", 4, LagPolicy::Block);
+    other_app
+        .set_text(Selection::Clipboard, "meeting notes")
+        .unwrap();
+    let snapshot = clipboard.capture(Selection::Clipboard).unwrap();
+    bus.publish(ClipboardEvent {
+        sequence: 7,
+        content: clipboard.describe(&snapshot).unwrap(),
+        snapshot,
+        mime_types: Vec::new(),
+        staged_path: None,
+        symlink_path: None,
+    })
+    .await;
+    drop(bus);
+    let started = std::time::Instant::now();
+    processor.start_processing(subscription).await;
+    // Hooks that replace the clipboard do not queue behind background ones
+    assert!(started.elapsed() < Duration::from_secs(2));
+
+    // The event reached the hook on stdin, the staged path as an argument
+    let event: Value = serde_json::from_slice(&std::fs::read(&event_path).unwrap()).unwrap();
+    assert_eq!(event["sequence"], 7);
+    assert_eq!(event["rule"], "notes");
+    let staged_path = event["staged_path"].as_str().unwrap();
+    assert_eq!(
+        std::fs::read_to_string(staged_path).unwrap(),
+        "meeting notes"
+    );
+
+    // The timed-out hook is killed and the upload's output goes on the clipboard
+    let file_name = std::path::Path::new(staged_path).file_name().unwrap();
+    assert_eq!(
+        other_app.get_text(Selection::Clipboard).unwrap(),
+        format!("https://bucket.example/7/{}", file_name.to_string_lossy())
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_hook_output_is_read_while_its_input_is_written() {
+    let dir = tempfile::tempdir().unwrap();
+    let config: Config = toml::from_str(
+        r#"
+[[watch.rules]]
+name = "notes"
+actions = ["stage", "rewrite"]
+
+[[watch.hooks]]
+name = "chatty"
+command = ["sh", "-c", "head -c 262144 /dev/zero | tr '\\0' x >&2; cat > /dev/null; echo done"]
+timeout_ms = 5000
+replace_clipboard = true
+"#,
+    )
+    .unwrap();
+
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+    let mut other_app = memory;
+
+    let processor_config = ProcessorConfig {
+        symlink_dir: dir.path().to_path_buf(),
+        enable_notifications: false,
+        rules: RuleSet::new(config.watch.rules.unwrap()).unwrap(),
+        hooks: config.watch.hooks,
+        ..Default::default()
+    };
+    let processor = ClipboardProcessor::new(
+        processor_config,
+        staging_file_manager(&dir).await,
+        clipboard.clone(),
+    );
+
+    // Both the event on stdin and the hook's stderr are larger than a pipe buffer
+    let bus = EventBus::new();
+    let subscription = bus.subscribe("processor", 1, LagPolicy::Block);
+    other_app
+        .set_text(Selection::Clipboard, &"notes ".repeat(50_000))
+        .unwrap();
+    let snapshot = clipboard.capture(Selection::Clipboard).unwrap();
+    bus.publish(ClipboardEvent {
+        sequence: 1,
+        content: clipboard.describe_in_full(&snapshot).unwrap(),
+        snapshot,
+        mime_types: Vec::new(),
+        staged_path: None,
+        symlink_path: None,
+    })
+    .await;
+    drop(bus);
+    let started = std::time::Instant::now();
+    processor.start_processing(subscription).await;
+
+    assert!(started.elapsed() < Duration::from_secs(3));
+    assert_eq!(other_app.get_text(Selection::Clipboard).unwrap(), "done");
+}
+
+/// Copies each of `texts` in another app, then runs the processor over them.
+async fn process_copies(
+    processor: ClipboardProcessor,
+    clipboard: &Arc<ClipboardManager>,
+    texts: &[&str],
+) {
+    let mut other_app = MemoryClipboard::new();
+    let bus = EventBus::new();
+    let subscription = bus.subscribe("processor", texts.len(), LagPolicy::Block);
+
+    for (n, text) in texts.iter().enumerate() {
+        other_app.set_text(Selection::Clipboard, text).unwrap();
+        let snapshot = ClipboardManager::with_backend(other_app.clone())
+            .capture(Selection::Clipboard)
+            .unwrap();
+        bus.publish(ClipboardEvent {
+            sequence: n as u64 + 1,
+            content: clipboard.describe(&snapshot).unwrap(),
+            snapshot,
+            mime_types: Vec::new(),
+            staged_path: None,
+            symlink_path: None,
+        })
+        .await;
+    }
+    drop(bus);
+    processor.start_processing(subscription).await;
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_processor_delivery_and_link_lifetime() {
+    let dir = tempfile::tempdir().unwrap();
+    let clipboard = Arc::new(ClipboardManager::with_backend(MemoryClipboard::new()));
+    let rules: Vec<RuleConfig> = vec![toml::from_str(
+        r#"match = { types = ["text/plain"] }
+actions = ["stage", "symlink"]"#,
+    )
+    .unwrap()];
+
+    // Copies for sandboxes that follow no links; equal names are numbered,
+    // and only copies the processor made are pruned
+    let copies = dir.path().join("copies");
+    std::fs::create_dir(&copies).unwrap();
+    let own_file = copies.join("claude-paste-my-notes.txt");
+    std::fs::write(&own_file, "mine").unwrap();
+    let processor = ClipboardProcessor::new(
+        ProcessorConfig {
+            symlink_dir: copies.clone(),
+            link_template: LinkTemplate::new("{slug}").unwrap(),
+            delivery: Delivery::Copy,
+            keep_symlinks: 2,
+            enable_notifications: false,
+            rules: RuleSet::new(rules.clone()).unwrap(),
+            ..Default::default()
+        },
+        staging_file_manager(&dir).await,
+        clipboard.clone(),
+    );
+    process_copies(
+        processor,
+        &clipboard,
+        &["pruned first", "Meeting notes!", "meeting notes"],
+    )
+    .await;
+
+    for (name, text) in [
+        ("claude-paste-meeting-notes.txt", "Meeting notes!"),
+        ("claude-paste-meeting-notes-2.txt", "meeting notes"),
+    ] {
+        let path = copies.join(name);
+        assert!(std::fs::symlink_metadata(&path).unwrap().is_file());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), text);
+    }
+    assert!(!copies.join("claude-paste-pruned-first.txt").exists());
+    assert_eq!(std::fs::read_to_string(&own_file).unwrap(), "mine");
+    assert!(LinkTemplate::new("{date}").is_err());
+
+    // Staged files outlive their age limit while symlinks point at them
+    let links = dir.path().join("links");
+    std::fs::create_dir(&links).unwrap();
+    let file_manager = Arc::new(
+        FileManager::new(FileManagerConfig {
+            staging_dir: dir.path().join("short-lived"),
+            cleanup_interval: Duration::from_millis(50),
+            max_file_age: Duration::ZERO,
+        })
+        .await
+        .unwrap(),
+    );
+    let processor = ClipboardProcessor::new(
+        ProcessorConfig {
+            symlink_dir: links.clone(),
+            link_template: LinkTemplate::new("{counter}").unwrap(),
+            enable_notifications: false,
+            rules: RuleSet::new(rules.clone()).unwrap(),
+            ..Default::default()
+        },
+        file_manager,
+        clipboard.clone(),
+    );
+    process_copies(processor, &clipboard, &["kept while linked"]).await;
+
+    let link = links.join("claude-paste-1.txt");
+    let staged = std::fs::read_link(&link).unwrap();
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(std::fs::read_to_string(&link).unwrap(), "kept while linked");
+
+    std::fs::remove_file(&link).unwrap();
+    std::fs::remove_file(links.join("claude-paste.txt")).unwrap();
+    sleep(Duration::from_millis(200)).await;
+    assert!(!staged.exists());
+
+    // Only the newest `keep_symlinks` links are kept, and what the pruned
+    // ones pointed at is then cleaned up
+    let pruned = dir.path().join("pruned");
+    std::fs::create_dir(&pruned).unwrap();
+    let file_manager = Arc::new(
+        FileManager::new(FileManagerConfig {
+            staging_dir: dir.path().join("pruned-staging"),
+            cleanup_interval: Duration::from_millis(50),
+            max_file_age: Duration::ZERO,
+        })
+        .await
+        .unwrap(),
+    );
+    for text in ["first copy", "second copy", "third copy"] {
+        let processor = ClipboardProcessor::new(
+            ProcessorConfig {
+                symlink_dir: pruned.clone(),
+                link_template: LinkTemplate::new("{slug}").unwrap(),
+                keep_symlinks: 1,
+                enable_notifications: false,
+                rules: RuleSet::new(rules.clone()).unwrap(),
+                ..Default::default()
+            },
+            file_manager.clone(),
+            clipboard.clone(),
+        );
+        process_copies(processor, &clipboard, &[text]).await;
+        sleep(Duration::from_millis(20)).await;
+    }
+    let mut names: Vec<_> = std::fs::read_dir(&pruned)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["claude-paste-third-copy.txt", "claude-paste.txt"]);
+    sleep(Duration::from_millis(200)).await;
+    let staged: Vec<_> = std::fs::read_dir(dir.path().join("pruned-staging"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(
+        staged,
+        [std::fs::read_link(pruned.join("claude-paste.txt")).unwrap()]
+    );
+
+    // Links made before a restart still keep their staged files
+    let earlier = dir.path().join("earlier");
+    std::fs::create_dir_all(earlier.join("staging")).unwrap();
+    std::fs::create_dir(earlier.join("links")).unwrap();
+    let (linked, unlinked) = (
+        earlier.join("staging/clip-linked.txt"),
+        earlier.join("staging/clip-unlinked.txt"),
+    );
+    std::fs::write(&linked, "linked").unwrap();
+    std::fs::write(&unlinked, "unlinked").unwrap();
+    std::os::unix::fs::symlink(&linked, earlier.join("links/claude-paste-1.txt")).unwrap();
+    let restarted = FileManager::new(FileManagerConfig {
+        staging_dir: earlier.join("staging"),
+        cleanup_interval: Duration::from_millis(50),
+        max_file_age: Duration::ZERO,
+    })
+    .await
+    .unwrap();
+    restarted.keep_linked_from(earlier.join("links"));
+    sleep(Duration::from_millis(200)).await;
+    assert!(linked.exists());
+    assert!(!unlinked.exists());
+}
+
+#[tokio::test]
+async fn test_event_bus_lag_policies() {
+    let memory = MemoryClipboard::new();
+    let clipboard = ClipboardManager::with_backend(memory.clone());
+    let mut other_app = memory;
+
+    let bus = EventBus::new();
+    let mut oldest_dropped = bus.subscribe("sse", 2, LagPolicy::DropOldest);
+    let mut newest_dropped = bus.subscribe("history", 2, LagPolicy::DropNewest);
+    let mut disconnected = bus.subscribe("webhook", 2, LagPolicy::Disconnect);
+    let detached = bus.subscribe("detached", 2, LagPolicy::Block);
+    drop(detached);
+    assert_eq!(bus.subscriber_count(), 3);
+
+    for n in 1..=4 {
+        other_app
+            .set_text(Selection::Clipboard, &format!("copy {n}"))
+            .unwrap();
+        let snapshot = clipboard.capture(Selection::Clipboard).unwrap();
+        bus.publish(ClipboardEvent {
+            sequence: n,
+            content: clipboard.describe(&snapshot).unwrap(),
+            snapshot,
+            mime_types: Vec::new(),
+            staged_path: None,
+            symlink_path: None,
+        })
+        .await;
+    }
+
+    // The webhook fell behind on the third event
+    assert_eq!(bus.subscriber_count(), 2);
+    drop(bus);
+
+    async fn texts(subscription: &mut Subscription) -> Vec<String> {
+        let mut texts = Vec::new();
+        while let Some(event) = subscription.recv().await {
+            texts.push(event.snapshot.text().unwrap());
+        }
+        texts
+    }
+
+    assert_eq!(texts(&mut oldest_dropped).await, ["copy 3", "copy 4"]);
+    assert_eq!(texts(&mut newest_dropped).await, ["copy 1", "copy 2"]);
+    assert_eq!(texts(&mut disconnected).await, ["copy 1", "copy 2"]);
+}
+
+#[tokio::test]
+async fn test_mcp_streamable_http_clipboard_get() {
+    let dir = tempfile::tempdir().unwrap();
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+    let app = test_server(clipboard, &dir).await.router();
+
+    let mut other_app = memory;
+    other_app
+        .set_text(Selection::Clipboard, "from the clipboard")
+        .unwrap();
+
+    let request = Request::post("/mcp")
+        .header("content-type", "application/json")
+        .header("accept", "application/json")
+        .body(Body::from(
+            json!({"jsonrpc": "2.0", "id": 1, "method": "initialize",
+                   "params": {"protocolVersion": "2025-03-26", "capabilities": {}}})
+            .to_string(),
+        ))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let session = response
+        .headers()
+        .get("mcp-session-id")
+        .expect("initialize must issue a session id")
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    // Requests outside a session are rejected
+    let (status, _) = post_json(
+        &app,
+        "/mcp",
+        None,
+        json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Web pages from elsewhere cannot drive the server; local ones can
+    for (origin, expected) in [
+        ("https://evil.example", StatusCode::FORBIDDEN),
+        ("null", StatusCode::FORBIDDEN),
+        ("http://localhost:6274", StatusCode::OK),
+    ] {
+        let request = Request::post("/mcp")
+            .header("content-type", "application/json")
+            .header("origin", origin)
+            .header("mcp-session-id", &session)
+            .body(Body::from(
+                json!({"jsonrpc": "2.0", "id": 2, "method": "ping"}).to_string(),
+            ))
+            .unwrap();
+        assert_eq!(
+            app.clone().oneshot(request).await.unwrap().status(),
+            expected
+        );
+    }
+
+    // Notifications are accepted without a body
+    let (status, _) = post_json(
+        &app,
+        "/mcp",
+        Some(&session),
+        json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+
+    let (status, body) = post_json(
+        &app,
+        "/rpc",
+        None,
+        json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call",
+               "params": {"name": "clipboard.get", "arguments": {}}}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let text = body["result"]["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("from the clipboard"));
+
+    let delete = Request::delete("/mcp")
+        .header("mcp-session-id", &session)
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(
+        app.clone().oneshot(delete).await.unwrap().status(),
+        StatusCode::OK
+    );
+
+    let (status, _) = post_json(
+        &app,
+        "/mcp",
+        Some(&session),
+        json!({"jsonrpc": "2.0", "id": 4, "method": "tools/list"}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_mcp_stdio_transport() {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let dir = tempfile::tempdir().unwrap();
+    let clipboard = Arc::new(ClipboardManager::with_backend(MemoryClipboard::new()));
+    let server = test_server(clipboard, &dir).await;
+
+    let (client, server_io) = tokio::io::duplex(64 * 1024);
+    let (server_read, server_write) = tokio::io::split(server_io);
+    tokio::spawn(server.serve_stdio(server_read, server_write));
+
+    let (client_read, mut client_write) = tokio::io::split(client);
+    let mut lines = BufReader::new(client_read).lines();
+
+    let messages = [
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize",
+               "params": {"protocolVersion": "2024-11-05", "capabilities": {}}}),
+        // Notifications must not produce a reply
+        json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}),
+    ];
+    for message in messages {
+        client_write
+            .write_all(format!("{message}\n").as_bytes())
+            .await
+            .unwrap();
+    }
+
+    let replies = next_replies(&mut lines, 2).await;
+    let (init, tools) = (&replies[0], &replies[1]);
+    assert_eq!(init["id"], 1);
+    assert_eq!(init["result"]["protocolVersion"], "2024-11-05");
+
+    assert_eq!(tools["id"], 2);
+    assert!(tools["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|tool| tool["name"] == "clipboard.get"));
+}
+
+#[tokio::test]
+async fn test_mcp_stdio_wait_does_not_block_other_requests() {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let dir = tempfile::tempdir().unwrap();
+    let clipboard = Arc::new(ClipboardManager::with_backend(MemoryClipboard::new()));
+    let server = test_server(clipboard, &dir)
+        .await
+        .with_events(EventBus::new());
+
+    let (client, server_io) = tokio::io::duplex(64 * 1024);
+    let (server_read, server_write) = tokio::io::split(server_io);
+    tokio::spawn(server.serve_stdio(server_read, server_write));
+
+    let (client_read, mut client_write) = tokio::io::split(client);
+    let mut lines = BufReader::new(client_read).lines();
+
+    let messages = [
+        json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call",
+               "params": {"name": "clipboard.wait", "arguments": {"timeout_ms": 500}}}),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "ping"}),
+    ];
+    for message in messages {
+        client_write
+            .write_all(format!("{message}\n").as_bytes())
+            .await
+            .unwrap();
+    }
+
+    // The ping is answered while the wait is still waiting
+    assert_eq!(next_message(&mut lines).await["id"], 2);
+
+    let wait = next_message(&mut lines).await;
+    assert_eq!(wait["id"], 1);
+    let result: Value =
+        serde_json::from_str(wait["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(result["changed"], false);
+}
+
+/// The next `count` lines the server writes, as JSON in id order; requests
+/// are answered as they finish.
+async fn next_replies<R: tokio::io::AsyncBufRead + Unpin>(
+    lines: &mut tokio::io::Lines<R>,
+    count: usize,
+) -> Vec<Value> {
+    let mut replies = Vec::new();
+    for _ in 0..count {
+        replies.push(next_message(lines).await);
+    }
+    replies.sort_by_key(|reply| reply["id"].as_u64());
+    replies
+}
+
+/// The next line the server writes, as JSON.
+async fn next_message<R: tokio::io::AsyncBufRead + Unpin>(
+    lines: &mut tokio::io::Lines<R>,
+) -> Value {
+    let line = tokio::time::timeout(Duration::from_secs(5), lines.next_line())
+        .await
+        .expect("Timed out waiting for the server")
+        .unwrap()
+        .unwrap();
+    serde_json::from_str(&line).unwrap()
+}
+
+#[tokio::test]
+async fn test_mcp_clipboard_notifications() {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let dir = tempfile::tempdir().unwrap();
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+    let mut other_app = memory;
+    let bus = EventBus::new();
+    let server = test_server(clipboard.clone(), &dir)
+        .await
+        .with_events(bus.clone());
+
+    let (client, server_io) = tokio::io::duplex(64 * 1024);
+    let (server_read, server_write) = tokio::io::split(server_io);
+    tokio::spawn(server.serve_stdio(server_read, server_write));
+
+    let (client_read, mut client_write) = tokio::io::split(client);
+    let mut lines = BufReader::new(client_read).lines();
+
+    let messages = [
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize",
+               "params": {"protocolVersion": "2025-03-26", "capabilities": {}}}),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "resources/subscribe",
+               "params": {"uri": "clipboard://current"}}),
+    ];
+    for message in messages {
+        client_write
+            .write_all(format!("{message}\n").as_bytes())
+            .await
+            .unwrap();
+    }
+
+    let replies = next_replies(&mut lines, 2).await;
+    assert_eq!(
+        replies[0]["result"]["capabilities"]["resources"]["subscribe"],
+        true
+    );
+    assert_eq!(replies[1]["id"], 2);
+
+    // Large enough to be staged rather than inlined
+    let long_text = "x".repeat(100_000);
+    other_app
+        .set_text(Selection::Clipboard, &long_text)
+        .unwrap();
+    let snapshot = clipboard.capture(Selection::Clipboard).unwrap();
+    bus.publish(ClipboardEvent {
+        sequence: 1,
+        content: clipboard.describe(&snapshot).unwrap(),
+        snapshot,
+        mime_types: vec!["text/plain".to_string()],
+        staged_path: None,
+        symlink_path: None,
+    })
+    .await;
+
+    let updated = next_message(&mut lines).await;
+    assert_eq!(updated["method"], "notifications/resources/updated");
+    assert_eq!(updated["params"]["uri"], "clipboard://current");
+
+    let changed = next_message(&mut lines).await;
+    assert_eq!(changed["method"], "notifications/clipboard/changed");
+    assert_eq!(changed["params"]["type"], "text/plain");
+    assert_eq!(changed["params"]["size"], 100_000);
+    // Nothing staged it, and notifying does not either
+    assert!(changed["params"]["staged_path"].is_null());
+    assert!(std::fs::read_dir(dir.path().join("staging"))
+        .unwrap()
+        .next()
+        .is_none());
+
+    // The resource reads back what was copied
+    client_write
+        .write_all(
+            format!(
+                "{}\n",
+                json!({"jsonrpc": "2.0", "id": 3, "method": "resources/read",
+                       "params": {"uri": "clipboard://current"}})
+            )
+            .as_bytes(),
+        )
+        .await
+        .unwrap();
+    let read = next_message(&mut lines).await;
+    assert_eq!(read["result"]["contents"][0]["text"], long_text.as_str());
+}
+
+#[tokio::test]
+async fn test_clipboard_wait_tool() {
+    let dir = tempfile::tempdir().unwrap();
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+    let bus = EventBus::new();
+    let app = test_server(clipboard.clone(), &dir)
+        .await
+        .with_events(bus.clone())
+        .router();
+
+    let watcher = ClipboardWatcher::new(clipboard, Duration::from_millis(20), bus);
+    tokio::spawn(watcher.start_watching());
+
+    let wait = |arguments: Value| {
+        let app = app.clone();
+        async move {
+            let (_, response) = post_json(
+                &app,
+                "/rpc",
+                None,
+                json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call",
+                       "params": {"name": "clipboard.wait", "arguments": arguments}}),
+            )
+            .await;
+            let text = response["result"]["content"][0]["text"].as_str().unwrap();
+            serde_json::from_str::<Value>(text).unwrap()
+        }
+    };
+
+    // Nothing has been copied yet
+    let idle = wait(json!({"after": 0, "timeout_ms": 100})).await;
+    assert_eq!(idle["changed"], false);
+    assert_eq!(idle["sequence"], 0);
+
+    let mut other_app = memory;
+    tokio::spawn(async move {
+        sleep(Duration::from_millis(100)).await;
+        other_app
+            .set_text(Selection::Clipboard, "copied while waiting")
+            .unwrap();
+    });
+
+    let copied = wait(json!({"timeout_ms": 5000})).await;
+    assert_eq!(copied["changed"], true);
+    assert_eq!(copied["sequence"], 1);
+    assert_eq!(copied["content"]["data"], "copied while waiting");
+
+    // A change the client has not seen yet returns at once
+    let missed = wait(json!({"after": 0, "timeout_ms": 5000})).await;
+    assert_eq!(missed["sequence"], 1);
+    assert_eq!(missed["content"]["data"], "copied while waiting");
+}
+
+/// A plugin that ignores its input and always returns `output`.
+#[cfg(feature = "plugins")]
+fn fixed_output_plugin(dir: &tempfile::TempDir, name: &str, output: &Value) -> std::path::PathBuf {
+    let output = output.to_string();
+    let path = dir.path().join(format!("{name}.wat"));
+    let wat = format!(
+        r#"(module
+  (memory (export "memory") 2)
+  (data (i32.const 0) "{}")
+  (func (export "alloc") (param i32) (result i32) (i32.const 4096))
+  (func (export "process") (param i32 i32) (result i64) (i64.const {})))"#,
+        output.replace('\\', "\\\\").replace('"', "\\\""),
+        output.len()
+    );
+    std::fs::write(&path, wat).unwrap();
+    path
+}
+
+#[cfg(feature = "plugins")]
+#[tokio::test]
+async fn test_plugins_in_watch_pipeline_and_as_tools() {
+    let dir = tempfile::tempdir().unwrap();
+    let redact = fixed_output_plugin(
+        &dir,
+        "redact",
+        &json!({"content": {"type": "text/plain", "data": "[redacted]"},
+                "metadata": {"redacted": true}}),
+    );
+    let block = fixed_output_plugin(&dir, "block", &json!({"veto": "looks like a key"}));
+    let config: Config = toml::from_str(&format!(
+        "[[plugins]]\nname = \"redact\"\npath = {:?}\n\n\
+         [[plugins]]\nname = \"block\"\npath = {:?}\nwatch = false\n",
+        redact, block
+    ))
+    .unwrap();
+    let plugins = claude_utils::plugins::PluginHost::load(config.plugins.clone()).unwrap();
+
+    // In the pipeline the rewritten text is what gets published and staged
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+    let (watcher, mut events) =
+        watcher_with_subscriber(clipboard.clone(), Duration::from_millis(10));
+    tokio::spawn(watcher.with_plugins(plugins.clone()).start_watching());
+
+    let mut other_app = memory;
+    other_app
+        .set_text(Selection::Clipboard, "token sk-livekey")
+        .unwrap();
+    let event = tokio::time::timeout(Duration::from_secs(2), events.recv())
+        .await
+        .expect("Timed out waiting for clipboard event")
+        .expect("Watcher stopped");
+    assert_eq!(event.snapshot.text().as_deref(), Some("[redacted]"));
+    assert!(
+        matches!(&event.content.content, ClipboardContent::Text { data, .. } if data == "[redacted]")
+    );
+    assert_eq!(event.content.metadata.annotations["redacted"], true);
+
+    // Plugins see the whole of a copy too large to publish inline
+    let (watcher, mut events) =
+        watcher_with_subscriber(clipboard.clone(), Duration::from_millis(10));
+    let path = dir.path().join("measure.wat");
+    std::fs::write(
+        &path,
+        r#"(module
+  (memory (export "memory") 4)
+  (data (i32.const 0) "{\"metadata\": {\"whole\": true}}")
+  (data (i32.const 1024) "{\"veto\": \"cut short\"}")
+  (func (export "alloc") (param i32) (result i32) (i32.const 4096))
+  (func (export "process") (param i32 i32) (result i64)
+    (if (result i64) (i32.gt_u (local.get 1) (i32.const 100000))
+      (then (i64.const 29))
+      (else (i64.const 0x40000000015)))))"#,
+    )
+    .unwrap();
+    let measure: Config = toml::from_str(&format!(
+        "[[plugins]]\nname = \"measure\"\npath = {path:?}\n"
+    ))
+    .unwrap();
+    let measure = claude_utils::plugins::PluginHost::load(measure.plugins).unwrap();
+    tokio::spawn(watcher.with_plugins(measure).start_watching());
+    sleep(Duration::from_millis(50)).await;
+
+    let large = "x".repeat(100_000);
+    other_app.set_text(Selection::Clipboard, &large).unwrap();
+    let event = tokio::time::timeout(Duration::from_secs(2), events.recv())
+        .await
+        .expect("Timed out waiting for clipboard event")
+        .expect("Watcher stopped");
+    assert_eq!(event.content.metadata.annotations["whole"], true);
+    assert_eq!(event.snapshot.text(), Some(large));
+
+    // A veto withholds the copy
+    let (watcher, mut events) =
+        watcher_with_subscriber(clipboard.clone(), Duration::from_millis(10));
+    let mut veto_config = config.plugins[1].clone();
+    veto_config.watch = true;
+    let veto = claude_utils::plugins::PluginHost::load(vec![veto_config]).unwrap();
+    tokio::spawn(watcher.with_plugins(veto).start_watching());
+    sleep(Duration::from_millis(50)).await;
+    assert!(events.try_recv().is_none());
+
+    // Both are offered as MCP tools
+    let app = test_server(clipboard, &dir)
+        .await
+        .with_plugins(plugins)
+        .router();
+    let (_, response) = post_json(
+        &app,
+        "/rpc",
+        None,
+        json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"}),
+    )
+    .await;
+    let tools: Vec<_> = response["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tool| tool["name"].as_str().unwrap().to_string())
+        .collect();
+    assert!(tools.contains(&"plugin.redact".to_string()));
+    assert!(tools.contains(&"plugin.block".to_string()));
+
+    let (_, response) = post_json(
+        &app,
+        "/rpc",
+        None,
+        json!({"jsonrpc": "2.0", "id": 2, "method": "tools/call",
+               "params": {"name": "plugin.block", "arguments": {}}}),
+    )
+    .await;
+    let text = response["result"]["content"][0]["text"].as_str().unwrap();
+    let output: Value = serde_json::from_str(text).unwrap();
+    assert_eq!(output["veto"], "looks like a key");
+}
+
 #[tokio::test]
 async fn test_file_staging() {
     let file_manager = FileManager::new(FileManagerConfig::default())
@@ -84,3 +1899,634 @@ fn test_mcp_protocol_serialization() {
     assert!(response_json.contains("\"result\""));
     assert!(!response_json.contains("\"error\""));
 }
+
+#[test]
+fn test_mcp_initialize_uses_camel_case() {
+    use claude_utils::mcp::protocol::*;
+
+    let response = InitializeResponse {
+        protocol_version: PROTOCOL_VERSION.to_string(),
+        capabilities: ServerCapabilities {
+            tools: Some(ToolsCapability {}),
+            resources: None,
+            prompts: None,
+        },
+        server_info: Some(ServerInfo {
+            name: "claude-utils-clipboard".to_string(),
+            version: None,
+        }),
+    };
+
+    let json = serde_json::to_value(&response).expect("Failed to serialize");
+    assert_eq!(json["protocolVersion"], PROTOCOL_VERSION);
+    assert!(json.get("serverInfo").is_some());
+
+    let request: InitializeRequest = serde_json::from_value(serde_json::json!({
+        "protocolVersion": "2024-11-05",
+        "capabilities": {},
+        "clientInfo": {"name": "claude-code"}
+    }))
+    .expect("Failed to parse initialize request");
+    assert_eq!(request.protocol_version, "2024-11-05");
+}
+
+#[tokio::test]
+async fn test_mcp_session_lifecycle() {
+    use claude_utils::mcp::session::SessionManager;
+
+    let sessions = SessionManager::new();
+    let session = sessions.create().await;
+
+    assert!(session.id.chars().all(|c| c.is_ascii_hexdigit()));
+    assert!(sessions.get(&session.id).await.is_some());
+
+    // Messages reach streams opened on the session
+    let mut stream = session.subscribe();
+    session.send(serde_json::json!({"jsonrpc": "2.0", "method": "ping"}));
+    assert_eq!(stream.recv().await.unwrap()["method"], "ping");
+
+    assert!(sessions.remove(&session.id).await);
+    assert!(sessions.get(&session.id).await.is_none());
+    assert!(!sessions.remove(&session.id).await);
+
+    // Past the maximum the least recently used session goes
+    let sessions = SessionManager::with_limits(Duration::from_secs(3600), 2);
+    let first = sessions.create().await;
+    let second = sessions.create().await;
+    sleep(Duration::from_millis(5)).await;
+    assert!(sessions.get(&first.id).await.is_some());
+    let third = sessions.create().await;
+    assert!(sessions.get(&first.id).await.is_some());
+    assert!(sessions.get(&second.id).await.is_none());
+    assert!(sessions.get(&third.id).await.is_some());
+
+    // Unused sessions expire, unless a stream is open on them
+    let sessions = SessionManager::with_limits(Duration::from_millis(20), 8);
+    let idle = sessions.create().await;
+    let streaming = sessions.create().await;
+    let _stream = streaming.subscribe();
+    sleep(Duration::from_millis(50)).await;
+    assert!(sessions.get(&idle.id).await.is_none());
+    assert!(sessions.get(&streaming.id).await.is_some());
+}
+
+#[test]
+fn test_file_clipboard_shared_between_instances() {
+    use claude_utils::clipboard::FileClipboard;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state").join("clipboard.json");
+
+    let daemon = ClipboardManager::with_backend(FileClipboard::new(&path).unwrap());
+    assert!(daemon.get_content().is_err());
+
+    // Another process writes through its own handle on the same file
+    let mut other_process = FileClipboard::new(&path).unwrap();
+    other_process
+        .set_text(Selection::Clipboard, "written elsewhere")
+        .unwrap();
+
+    match daemon.get_content().unwrap().content {
+        ClipboardContent::Text { data, .. } => assert_eq!(data, "written elsewhere"),
+        _ => panic!("Expected text content"),
+    }
+
+    other_process
+        .set_image(
+            Selection::Clipboard,
+            &RawImage {
+                width: 1,
+                height: 2,
+                bytes: vec![1, 2, 3, 255, 4, 5, 6, 255],
+            },
+        )
+        .unwrap();
+
+    match daemon.get_content().unwrap().content {
+        ClipboardContent::ImagePng { width, height, .. } => assert_eq!((width, height), (1, 2)),
+        _ => panic!("Expected image content"),
+    }
+
+    // Only the owner can read it
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // A file that cannot be read is not written over
+    std::fs::write(&path, "{not json").unwrap();
+    assert!(other_process
+        .set_text(Selection::Primary, "would lose the rest")
+        .is_err());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "{not json");
+}
+
+#[test]
+fn test_cli_file_backend_roundtrip() {
+    use std::process::Command;
+
+    let dir = tempfile::tempdir().unwrap();
+    let clipboard_file = dir.path().join("clipboard.json");
+    let cli = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_claude-utils"))
+            .args(["--backend", "file", "--clipboard-file"])
+            .arg(&clipboard_file)
+            .args(args)
+            .output()
+            .expect("Failed to run claude-utils")
+    };
+
+    assert!(cli(&["clip", "set", "hello from ssh"]).status.success());
+
+    let output = cli(&["clip", "get", "--format", "text"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello from ssh\n");
+
+    let output = cli(&["clip", "paste"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello from ssh");
+}
+
+#[tokio::test]
+async fn test_html_clipboard_as_markdown() {
+    let dir = tempfile::tempdir().unwrap();
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+
+    let html = r#"<p>See <a href="https://example.com/docs">the docs</a></p>
+<ul><li>first</li><li>second</li></ul>
+<pre><code>let x = 1;</code></pre>
+<table><tr><th>Name</th><th>Size</th></tr><tr><td>a.png</td><td>42</td></tr></table>"#;
+
+    let mut browser = memory;
+    browser
+        .set_formats(
+            Selection::Clipboard,
+            &[
+                ("text/html".to_string(), html.as_bytes().to_vec()),
+                (
+                    "text/plain".to_string(),
+                    b"See the docs first second".to_vec(),
+                ),
+            ],
+        )
+        .unwrap();
+
+    let content = clipboard.get_content().unwrap().content;
+    assert!(matches!(&content, ClipboardContent::Html { data, .. } if data == html));
+
+    match content.into_markdown() {
+        ClipboardContent::Markdown { data, .. } => {
+            assert!(data.contains("[the docs](https://example.com/docs)"));
+            assert!(data.contains("* first"));
+            assert!(data.contains("```"));
+            // Header, separator and one row
+            assert_eq!(data.lines().filter(|l| l.starts_with('|')).count(), 3);
+        }
+        _ => panic!("Expected markdown content"),
+    }
+
+    // Explicit text requests get the flattened rendering instead
+    let app = test_server(clipboard, &dir).await.router();
+    let (_, body) = post_json(
+        &app,
+        "/rpc",
+        None,
+        json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call",
+               "params": {"name": "clipboard.get", "arguments": {"format": "text"}}}),
+    )
+    .await;
+    let text: Value =
+        serde_json::from_str(body["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(text["content"]["type"], "text/plain");
+    assert_eq!(text["content"]["data"], "See the docs first second");
+}
+
+#[tokio::test]
+async fn test_copied_files_are_inlined_and_staged() {
+    use claude_utils::clipboard::files::to_uri_list;
+
+    let dir = tempfile::tempdir().unwrap();
+    let notes = dir.path().join("release notes.md");
+    std::fs::write(&notes, "# v0.2\n").unwrap();
+    let screenshot = dir.path().join("screenshot.png");
+    image::RgbaImage::from_pixel(3, 2, image::Rgba([9, 9, 9, 255]))
+        .save(&screenshot)
+        .unwrap();
+
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+
+    // File managers offer the paths as plain text too
+    let mut file_manager = memory;
+    file_manager
+        .set_formats(
+            Selection::Clipboard,
+            &[
+                (
+                    "text/uri-list".to_string(),
+                    to_uri_list(&[&notes, &screenshot]).into_bytes(),
+                ),
+                ("text/plain".to_string(), b"release notes.md".to_vec()),
+            ],
+        )
+        .unwrap();
+
+    match clipboard.get_content().unwrap().content {
+        ClipboardContent::Files { files } => {
+            assert_eq!(files.len(), 2);
+            assert_eq!(files[0].path, notes.to_string_lossy());
+            assert_eq!(files[0].size, 7);
+            assert_eq!(files[1].mime_type, "image/png");
+        }
+        _ => panic!("Expected a file list"),
+    }
+
+    let app = test_server(clipboard, &dir).await.router();
+    let (_, body) = post_json(
+        &app,
+        "/rpc",
+        None,
+        json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call",
+               "params": {"name": "clipboard.get", "arguments": {}}}),
+    )
+    .await;
+    let result: Value =
+        serde_json::from_str(body["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+    let files = &result["content"]["files"];
+
+    assert_eq!(result["content"]["type"], "text/uri-list");
+    assert_eq!(files[0]["data"], "# v0.2\n");
+    let staged = files[1]["file"].as_str().expect("Image should be staged");
+    assert_eq!(
+        std::fs::read(staged).unwrap(),
+        std::fs::read(&screenshot).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_primary_selection_is_separate() {
+    let dir = tempfile::tempdir().unwrap();
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+
+    // Selecting text in a terminal fills PRIMARY without touching CLIPBOARD
+    let mut terminal = memory;
+    terminal
+        .set_text(Selection::Clipboard, "copied with ctrl-c")
+        .unwrap();
+    terminal
+        .set_text(Selection::Primary, "highlighted")
+        .unwrap();
+
+    let primary = clipboard.get_content_from(Selection::Primary).unwrap();
+    assert_eq!(primary.metadata.selection, Selection::Primary);
+    assert!(
+        matches!(primary.content, ClipboardContent::Text { data, .. } if data == "highlighted")
+    );
+    assert!(clipboard.get_content_from(Selection::Secondary).is_err());
+
+    let app = test_server(clipboard.clone(), &dir).await.router();
+    let (_, body) = post_json(
+        &app,
+        "/rpc",
+        None,
+        json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call",
+               "params": {"name": "clipboard.set",
+                          "arguments": {"type": "text/plain", "data": "pasted", "selection": "secondary"}}}),
+    )
+    .await;
+    assert!(body.get("error").is_none(), "{body}");
+
+    let (_, body) = post_json(
+        &app,
+        "/rpc",
+        None,
+        json!({"jsonrpc": "2.0", "id": 2, "method": "tools/call",
+               "params": {"name": "clipboard.get", "arguments": {"selection": "secondary"}}}),
+    )
+    .await;
+    let text: Value =
+        serde_json::from_str(body["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(text["content"]["data"], "pasted");
+    assert_eq!(text["metadata"]["selection"], "secondary");
+
+    let (_, body) = post_json(
+        &app,
+        "/rpc",
+        None,
+        json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call",
+               "params": {"name": "clipboard.get", "arguments": {"selection": "middle"}}}),
+    )
+    .await;
+    assert_eq!(body["error"]["code"], -32602);
+
+    match clipboard.get_content().unwrap().content {
+        ClipboardContent::Text { data, .. } => assert_eq!(data, "copied with ctrl-c"),
+        _ => panic!("Expected text content"),
+    }
+}
+
+#[tokio::test]
+async fn test_list_and_fetch_exact_targets() {
+    let dir = tempfile::tempdir().unwrap();
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+
+    let blob = vec![0xAB; claude_utils::MAX_INLINE_SIZE + 1];
+    let mut editor = memory;
+    editor
+        .set_formats(
+            Selection::Clipboard,
+            &[
+                ("application/x-editor-state".to_string(), vec![0, 159, 146]),
+                ("application/x-large-blob".to_string(), blob.clone()),
+                ("text/plain".to_string(), b"plain".to_vec()),
+            ],
+        )
+        .unwrap();
+
+    let app = test_server(clipboard, &dir).await.router();
+    let call = |id: u64, name: &str, arguments: Value| {
+        json!({"jsonrpc": "2.0", "id": id, "method": "tools/call",
+               "params": {"name": name, "arguments": arguments}})
+    };
+    let tool_json = |body: &Value| -> Value {
+        serde_json::from_str(body["result"]["content"][0]["text"].as_str().unwrap()).unwrap()
+    };
+
+    let (_, body) = post_json(
+        &app,
+        "/rpc",
+        None,
+        call(1, "clipboard.list_formats", json!({})),
+    )
+    .await;
+    let formats = tool_json(&body)["formats"].clone();
+    assert_eq!(
+        formats,
+        json!([
+            {"mime": "application/x-editor-state", "size": 3},
+            {"mime": "application/x-large-blob", "size": blob.len()},
+            {"mime": "text/plain", "size": 5},
+        ])
+    );
+
+    let (_, body) = post_json(
+        &app,
+        "/rpc",
+        None,
+        call(
+            2,
+            "clipboard.get",
+            json!({"mime": "application/x-editor-state"}),
+        ),
+    )
+    .await;
+    let content = tool_json(&body)["content"].clone();
+    assert_eq!(content["type"], "target");
+    assert_eq!(content["encoding"], "base64");
+    assert_eq!(content["data"], "AJ+S");
+
+    let (_, body) = post_json(
+        &app,
+        "/rpc",
+        None,
+        call(
+            3,
+            "clipboard.get",
+            json!({"mime": "application/x-large-blob"}),
+        ),
+    )
+    .await;
+    let content = tool_json(&body)["content"].clone();
+    assert!(content.get("data").is_none());
+    let staged = content["file"]
+        .as_str()
+        .expect("Large target should be staged");
+    assert_eq!(std::fs::read(staged).unwrap(), blob);
+}
+
+/// Memory clipboard that counts how often the image is read.
+struct CountingClipboard {
+    memory: MemoryClipboard,
+    image_reads: Arc<std::sync::atomic::AtomicUsize>,
+}
+
+impl ClipboardBackend for CountingClipboard {
+    fn name(&self) -> &'static str {
+        "counting"
+    }
+
+    fn get_text(&mut self, selection: Selection) -> claude_utils::Result<String> {
+        self.memory.get_text(selection)
+    }
+
+    fn set_text(&mut self, selection: Selection, text: &str) -> claude_utils::Result<()> {
+        self.memory.set_text(selection, text)
+    }
+
+    fn get_image(&mut self, selection: Selection) -> claude_utils::Result<RawImage> {
+        self.image_reads
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        self.memory.get_image(selection)
+    }
+
+    fn set_image(&mut self, selection: Selection, image: &RawImage) -> claude_utils::Result<()> {
+        self.memory.set_image(selection, image)
+    }
+
+    fn formats(&mut self, selection: Selection) -> claude_utils::Result<Vec<String>> {
+        self.memory.formats(selection)
+    }
+
+    fn get_format(&mut self, selection: Selection, mime: &str) -> claude_utils::Result<Vec<u8>> {
+        self.memory.get_format(selection, mime)
+    }
+
+    fn set_formats(
+        &mut self,
+        selection: Selection,
+        formats: &[(String, Vec<u8>)],
+    ) -> claude_utils::Result<()> {
+        self.memory.set_formats(selection, formats)
+    }
+}
+
+#[tokio::test]
+async fn test_clipboard_get_stages_the_image_it_read() {
+    use std::sync::atomic::Ordering;
+
+    let dir = tempfile::tempdir().unwrap();
+    let memory = MemoryClipboard::new();
+    let image_reads = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let clipboard = Arc::new(ClipboardManager::with_backend(CountingClipboard {
+        memory: memory.clone(),
+        image_reads: image_reads.clone(),
+    }));
+    let app = test_server(clipboard.clone(), &dir).await.router();
+
+    // Noise, so the PNG is too large to inline
+    let mut seed = 7u32;
+    let bytes = (0..200 * 200 * 4)
+        .map(|_| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 24) as u8
+        })
+        .collect();
+    let mut other_app = memory;
+    other_app
+        .set_image(
+            Selection::Clipboard,
+            &RawImage {
+                width: 200,
+                height: 200,
+                bytes,
+            },
+        )
+        .unwrap();
+
+    let snapshot = clipboard.capture(Selection::Clipboard).unwrap();
+    let reads_per_capture = image_reads.swap(0, Ordering::SeqCst);
+
+    let (_, body) = post_json(
+        &app,
+        "/rpc",
+        None,
+        json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call",
+               "params": {"name": "clipboard.get", "arguments": {"format": "image"}}}),
+    )
+    .await;
+    let text = body["result"]["content"][0]["text"].as_str().unwrap();
+    let content = serde_json::from_str::<Value>(text).unwrap()["content"].clone();
+    let staged = content["file"].as_str().expect("Image should be staged");
+
+    // Staged from the one capture, not read and encoded again
+    assert_eq!(image_reads.load(Ordering::SeqCst), reads_per_capture);
+    assert_eq!(std::fs::read(staged).unwrap(), snapshot.png().unwrap());
+}
+
+#[tokio::test]
+async fn test_clipboard_set_decodes_images() {
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+    use claude_utils::mcp::protocol::{INVALID_PARAMS, INVALID_REQUEST};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+    let dir = tempfile::tempdir().unwrap();
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+    let app = test_server(clipboard.clone(), &dir).await.router();
+
+    let mut png = Vec::new();
+    image::RgbaImage::from_pixel(2, 3, image::Rgba([255, 0, 0, 255]))
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+
+    let (_, body) = post_json(
+        &app,
+        "/rpc",
+        None,
+        json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call",
+               "params": {"name": "clipboard.set",
+                          "arguments": {"type": "image/png", "data": BASE64.encode(&png)}}}),
+    )
+    .await;
+    assert!(body.get("error").is_none(), "{body}");
+
+    let mut reader = memory.clone();
+    let image = reader.get_image(Selection::Clipboard).unwrap();
+    assert_eq!((image.width, image.height), (2, 3));
+    assert_eq!(&image.bytes[..4], &[255, 0, 0, 255]);
+
+    // A generated diagram on disk, with the type taken from its extension
+    let diagram = dir.path().join("diagram.jpg");
+    image::RgbImage::from_pixel(5, 4, image::Rgb([0, 0, 255]))
+        .save(&diagram)
+        .unwrap();
+    let set_file = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/call",
+                          "params": {"name": "clipboard.set",
+                                     "arguments": {"file": diagram.to_string_lossy()}}});
+
+    // Over HTTP no file is read for the client
+    let (_, body) = post_json(&app, "/rpc", None, set_file.clone()).await;
+    assert_eq!(body["error"]["code"], INVALID_PARAMS);
+
+    // Over stdio the client already runs as this user
+    let (client, server_io) = tokio::io::duplex(64 * 1024);
+    let (server_read, server_write) = tokio::io::split(server_io);
+    let server = test_server(clipboard.clone(), &dir).await;
+    tokio::spawn(server.serve_stdio(server_read, server_write));
+    let (client_read, mut client_write) = tokio::io::split(client);
+    let mut lines = tokio::io::BufReader::new(client_read).lines();
+    client_write
+        .write_all(format!("{set_file}\n").as_bytes())
+        .await
+        .unwrap();
+    let reply = next_message(&mut lines).await;
+    assert!(reply.get("error").is_none(), "{reply}");
+
+    let image = reader.get_image(Selection::Clipboard).unwrap();
+    assert_eq!((image.width, image.height), (5, 4));
+
+    // Without --write nothing may be set
+    let app = test_server(clipboard, &dir)
+        .await
+        .with_writes(false)
+        .router();
+    let (_, body) = post_json(
+        &app,
+        "/rpc",
+        None,
+        json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call",
+               "params": {"name": "clipboard.set",
+                          "arguments": {"type": "text/plain", "data": "refused"}}}),
+    )
+    .await;
+    assert_eq!(body["error"]["code"], INVALID_REQUEST);
+}
+
+#[tokio::test]
+async fn test_daemon_client_hands_off_writes() {
+    use claude_utils::mcp::client::DaemonClient;
+
+    let dir = tempfile::tempdir().unwrap();
+    let memory = MemoryClipboard::new();
+    let clipboard = Arc::new(ClipboardManager::with_backend(memory.clone()));
+    let app = test_server(clipboard, &dir).await.router();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let daemon = DaemonClient::connect("127.0.0.1", port)
+        .await
+        .expect("Daemon should answer");
+    assert_eq!(daemon.backend(), "memory");
+
+    daemon
+        .set_content(
+            Selection::Primary,
+            &ClipboardContent::Text {
+                data: "kept alive by the daemon".to_string(),
+                truncated: None,
+            },
+        )
+        .await
+        .unwrap();
+
+    let mut reader = memory;
+    assert_eq!(
+        reader.get_text(Selection::Primary).unwrap(),
+        "kept alive by the daemon"
+    );
+
+    // Nothing listening: the CLI falls back to holding the selection itself
+    drop(daemon);
+    let unused = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let closed_port = unused.local_addr().unwrap().port();
+    drop(unused);
+    assert!(DaemonClient::connect("127.0.0.1", closed_port)
+        .await
+        .is_none());
+}